@@ -0,0 +1,79 @@
+//! Times parsing and rendering Markdown to HTML on every Markdown file in
+//! `src/data`, and on a document of about 10k lines made by repeating them.
+//!
+//! Run with `cargo bench`, optionally followed by `-- <name>` to only time
+//! the inputs whose name contains `<name>`.
+
+use std::env;
+use std::fs;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const DATA_DIR: &str = "src/data";
+const LARGE_DOCUMENT_LINES: usize = 10_000;
+const MIN_RUN_TIME: Duration = Duration::from_secs(1);
+const MIN_ITERATIONS: u32 = 5;
+
+fn read_inputs() -> Vec<(String, String)> {
+    let mut inputs: Vec<(String, String)> = fs::read_dir(DATA_DIR)
+        .expect("src/data should be readable")
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "md"))
+        .map(|path| {
+            let name = path.file_name().unwrap().to_string_lossy().to_string();
+            (name, fs::read_to_string(&path).unwrap())
+        })
+        .collect();
+    inputs.sort();
+
+    let all_files = inputs
+        .iter()
+        .map(|(_, text)| text.as_str())
+        .collect::<Vec<&str>>()
+        .join("\n");
+    let mut large_document = String::new();
+    while large_document.lines().count() < LARGE_DOCUMENT_LINES {
+        large_document.push_str(&all_files);
+        large_document.push('\n');
+    }
+    inputs.push(("large-document".to_string(), large_document));
+
+    inputs
+}
+
+/// Mean time of one parse, running for at least `MIN_RUN_TIME` and
+/// `MIN_ITERATIONS` iterations after one warm-up run.
+fn time_parse(text: &str) -> (Duration, u32) {
+    black_box(md_parser::render_html(&md_parser::parse(text)));
+
+    let mut iterations = 0;
+    let start = Instant::now();
+    while iterations < MIN_ITERATIONS || start.elapsed() < MIN_RUN_TIME {
+        black_box(md_parser::render_html(&md_parser::parse(black_box(text))));
+        iterations += 1;
+    }
+    (start.elapsed() / iterations, iterations)
+}
+
+fn main() {
+    // `cargo bench` passes `--bench`; anything else is a name filter.
+    let filter = env::args().skip(1).find(|arg| !arg.starts_with("--"));
+
+    println!(
+        "{:<24} {:>8} {:>10} {:>14}",
+        "input", "lines", "runs", "time per run"
+    );
+    for (name, text) in read_inputs() {
+        if filter.as_ref().is_some_and(|filter| !name.contains(filter)) {
+            continue;
+        }
+        let (mean, iterations) = time_parse(&text);
+        println!(
+            "{:<24} {:>8} {:>10} {:>14?}",
+            name,
+            text.lines().count(),
+            iterations,
+            mean
+        );
+    }
+}
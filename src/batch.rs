@@ -0,0 +1,348 @@
+//! `--batch` mode: converts a directory of Markdown into a mirrored directory
+//! of HTML.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use md_parser::{Content, Node, Tag};
+
+use crate::cli::{render, Options};
+
+/// How many files a batch run converted, and how many it left alone because
+/// their output was already newer than their source.
+#[derive(Debug, PartialEq)]
+pub struct Summary {
+    pub converted: usize,
+    pub up_to_date: usize,
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} converted, {} up to date",
+            self.converted, self.up_to_date
+        )
+    }
+}
+
+/// Every `.md` file under `dir`, sorted. Directories are identified by their
+/// canonical path, and those in `visited` are not walked again, so symlinks
+/// that loop back up the tree are followed only once. An output directory
+/// inside the input directory goes in `visited` up front, so that earlier
+/// output is not converted again.
+fn markdown_files(
+    dir: &Path,
+    visited: &mut HashSet<PathBuf>,
+    files: &mut Vec<PathBuf>,
+) -> Result<(), String> {
+    let canonical =
+        fs::canonicalize(dir).map_err(|error| format!("{}: {}", dir.display(), error))?;
+    if !visited.insert(canonical) {
+        return Ok(());
+    }
+
+    let entries = fs::read_dir(dir).map_err(|error| format!("{}: {}", dir.display(), error))?;
+    let mut paths = entries
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<PathBuf>, _>>()
+        .map_err(|error| format!("{}: {}", dir.display(), error))?;
+    paths.sort();
+
+    for path in paths {
+        if path.is_dir() {
+            markdown_files(&path, visited, files)?;
+        } else if path.extension().is_some_and(|extension| extension == "md") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Whether `target` was written after `source` last changed. Anything that
+/// can't be read counts as out of date.
+fn is_up_to_date(source: &Path, target: &Path) -> bool {
+    let modified = |path: &Path| fs::metadata(path).and_then(|metadata| metadata.modified());
+    match (modified(source), modified(target)) {
+        (Ok(source), Ok(target)) => target >= source,
+        _ => false,
+    }
+}
+
+/// Points a relative link to a Markdown file at the converted HTML file,
+/// keeping any query or fragment: `guide.md#setup` becomes
+/// `guide.html#setup`. Returns `None` for every other URL.
+pub fn html_link(url: &str) -> Option<String> {
+    let (path, rest) = url.split_at(url.find(['?', '#']).unwrap_or(url.len()));
+    if path.starts_with('/') || path.contains(':') {
+        return None;
+    }
+    match path.strip_suffix(".md") {
+        Some(stem) if !stem.is_empty() && !stem.ends_with('/') => {
+            Some(format!("{}.html{}", stem, rest))
+        }
+        _ => None,
+    }
+}
+
+fn rewrite_links(node: &mut Node) {
+    if node.tag_name == Tag::A {
+        for (attr_label, attr_value) in node.attributes.0.iter_mut() {
+            if attr_label == "href" {
+                if let Some(link) = html_link(attr_value) {
+                    *attr_value = link;
+                }
+            }
+        }
+    }
+    for content in node.content.iter_mut() {
+        if let Content::InnerContent(child) = content {
+            rewrite_links(child);
+        }
+    }
+}
+
+fn convert_file(source: &Path, target: &Path, options: &Options) -> Result<(), String> {
+    let markdown =
+        fs::read_to_string(source).map_err(|error| format!("{}: {}", source.display(), error))?;
+    let mut document = md_parser::parse(&markdown);
+    for node in document.nodes.0.iter_mut() {
+        rewrite_links(node);
+    }
+    let html = render(&document, &[source.display().to_string()], options);
+
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent).map_err(|error| format!("{}: {}", parent.display(), error))?;
+    }
+    fs::write(target, html).map_err(|error| format!("{}: {}", target.display(), error))
+}
+
+/// Converts every `.md` file under `options.inputs[0]` into an `.html` file
+/// at the same relative path under `options.output`. When both are the same
+/// directory, the `.html` files are written next to the `.md` files.
+pub fn convert_tree(options: &Options) -> Result<Summary, String> {
+    let input_dir = Path::new(&options.inputs[0]);
+    let output_dir = Path::new(options.output.as_deref().unwrap_or_default());
+    if !input_dir.is_dir() {
+        return Err(format!("{}: not a directory", input_dir.display()));
+    }
+    fs::create_dir_all(output_dir)
+        .map_err(|error| format!("{}: {}", output_dir.display(), error))?;
+    let canonical =
+        |dir: &Path| fs::canonicalize(dir).map_err(|error| format!("{}: {}", dir.display(), error));
+    let (input_canonical, output_canonical) = (canonical(input_dir)?, canonical(output_dir)?);
+
+    let mut visited = HashSet::new();
+    if output_canonical != input_canonical && output_canonical.starts_with(&input_canonical) {
+        visited.insert(output_canonical);
+    }
+    let mut files = vec![];
+    markdown_files(input_dir, &mut visited, &mut files)?;
+    if files.is_empty() {
+        return Err(format!("{}: no .md files found", input_dir.display()));
+    }
+
+    let mut summary = Summary {
+        converted: 0,
+        up_to_date: 0,
+    };
+    for source in files {
+        let relative = source.strip_prefix(input_dir).unwrap_or(&source);
+        let target = output_dir.join(relative).with_extension("html");
+        if !options.force && is_up_to_date(&source, &target) {
+            summary.up_to_date += 1;
+            continue;
+        }
+        convert_file(&source, &target, options)?;
+        summary.converted += 1;
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs::{self, File};
+    use std::path::PathBuf;
+    use std::time::{Duration, SystemTime};
+
+    use md_parser::HtmlFlavor;
+
+    use crate::batch::{convert_tree, html_link, Summary};
+    use crate::cli::Options;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("md_parser-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn html_links() {
+        assert_eq!(html_link("guide.md"), Some("guide.html".to_string()));
+        assert_eq!(
+            html_link("../api/tables.md#alignment"),
+            Some("../api/tables.html#alignment".to_string())
+        );
+        assert_eq!(html_link("https://example.com/readme.md"), None);
+        assert_eq!(html_link("/docs/guide.md"), None);
+        assert_eq!(html_link("notes.txt"), None);
+        assert_eq!(html_link("#guide.md"), None);
+        assert_eq!(html_link(".md"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_cycles() {
+        let dir = temp_dir("symlinks");
+        let (docs, site) = (dir.join("docs"), dir.join("site"));
+        fs::create_dir_all(docs.join("sub")).unwrap();
+        fs::write(docs.join("sub/page.md"), "page").unwrap();
+        std::os::unix::fs::symlink("..", docs.join("sub/up")).unwrap();
+
+        let options = Options {
+            inputs: vec![docs.display().to_string()],
+            output: Some(site.display().to_string()),
+            flavor: HtmlFlavor::Html5,
+            standalone: false,
+            source_positions: false,
+            force: false,
+        };
+        assert_eq!(
+            convert_tree(&options),
+            Ok(Summary {
+                converted: 1,
+                up_to_date: 0
+            })
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn converts_a_tree() {
+        let dir = temp_dir("batch");
+        let (docs, site) = (dir.join("docs"), dir.join("site"));
+        fs::create_dir_all(docs.join("api")).unwrap();
+        fs::write(
+            docs.join("index.md"),
+            "[api](api/tables.md#top) [web](https://example.com/a.md)",
+        )
+        .unwrap();
+        fs::write(docs.join("api/tables.md"), "# Tables").unwrap();
+        fs::write(docs.join("notes.txt"), "not markdown").unwrap();
+
+        let options = Options {
+            inputs: vec![docs.display().to_string()],
+            output: Some(site.display().to_string()),
+            flavor: HtmlFlavor::Html5,
+            standalone: false,
+            source_positions: false,
+            force: false,
+        };
+        assert_eq!(
+            convert_tree(&options),
+            Ok(Summary {
+                converted: 2,
+                up_to_date: 0
+            })
+        );
+        assert_eq!(
+            fs::read_to_string(site.join("index.html")).unwrap(),
+            concat!(
+                r##"<p class="md-p"><a href="api/tables.html#top">api</a> "##,
+                r#"<a href="https://example.com/a.md">web</a></p>"#,
+                "\n",
+            )
+        );
+        assert!(site.join("api/tables.html").is_file());
+        assert!(!site.join("notes.txt").exists());
+        assert!(!site.join("notes.html").exists());
+
+        // Only the file whose output is older than its source is converted
+        // again.
+        let long_ago = SystemTime::now() - Duration::from_secs(3600);
+        File::options()
+            .write(true)
+            .open(site.join("index.html"))
+            .unwrap()
+            .set_modified(long_ago)
+            .unwrap();
+        assert_eq!(
+            convert_tree(&options),
+            Ok(Summary {
+                converted: 1,
+                up_to_date: 1
+            })
+        );
+
+        let forced = Options {
+            force: true,
+            ..options
+        };
+        assert_eq!(
+            convert_tree(&forced),
+            Ok(Summary {
+                converted: 2,
+                up_to_date: 0
+            })
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn output_directories() {
+        let dir = temp_dir("output");
+        let docs = dir.join("docs");
+        fs::create_dir_all(&docs).unwrap();
+        fs::write(docs.join("index.md"), "# Index").unwrap();
+        let options = |output: &PathBuf| Options {
+            inputs: vec![docs.display().to_string()],
+            output: Some(output.display().to_string()),
+            flavor: HtmlFlavor::Html5,
+            standalone: false,
+            source_positions: false,
+            force: false,
+        };
+
+        // The same directory converts in place.
+        assert_eq!(
+            convert_tree(&options(&docs)),
+            Ok(Summary {
+                converted: 1,
+                up_to_date: 0
+            })
+        );
+        assert!(docs.join("index.html").is_file());
+
+        // A directory inside the input is not read as input.
+        let site = docs.join("site");
+        fs::create_dir_all(&site).unwrap();
+        fs::write(site.join("stale.md"), "stale").unwrap();
+        assert_eq!(
+            convert_tree(&options(&site)),
+            Ok(Summary {
+                converted: 1,
+                up_to_date: 0
+            })
+        );
+        assert!(!site.join("site/stale.html").exists());
+
+        let empty = dir.join("empty");
+        fs::create_dir_all(&empty).unwrap();
+        let options = Options {
+            inputs: vec![empty.display().to_string()],
+            ..options(&site)
+        };
+        assert_eq!(
+            convert_tree(&options),
+            Err(format!("{}: no .md files found", empty.display()))
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,336 @@
+//! The `md_parser` command line: argument parsing and the conversion itself.
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::process::ExitCode;
+
+use crate::batch;
+use md_parser::{escape_html, Document, HtmlFlavor, RenderOptions};
+
+pub const USAGE: &str = "\
+Usage: md_parser [OPTIONS] [FILE]...
+       md_parser [OPTIONS] --batch DIR -o OUT_DIR
+
+Converts Markdown to HTML. FILEs are joined in order; with no FILE, or when
+FILE is -, Markdown is read from standard input.
+
+With --batch, every .md file under DIR is converted to an .html file at the
+same place under OUT_DIR, which may be DIR itself, and relative links to .md
+files are pointed at the .html files instead. Files whose output is newer than their source are skipped,
+even when the options differ from the last run; pass --force to convert
+everything again.
+
+Options:
+  -o, --output FILE      Write the HTML to FILE instead of standard output
+  -f, --format FORMAT    Output format: html (default) or xhtml
+  -s, --standalone       Wrap the output in a complete HTML document
+      --sourcepos        Add data-sourcepos attributes to block elements
+  -b, --batch DIR        Convert the directory DIR into the directory given by -o
+      --force            With --batch, convert files even if their output is newer
+  -h, --help             Print this help
+";
+
+/// Exit code for bad command-line arguments.
+const USAGE_ERROR: u8 = 2;
+
+#[derive(Debug, PartialEq)]
+pub struct Options {
+    /// Input paths, where `-` is standard input.
+    pub inputs: Vec<String>,
+    pub output: Option<String>,
+    pub flavor: HtmlFlavor,
+    pub standalone: bool,
+    pub source_positions: bool,
+    /// Converts every file in batch mode, up to date or not.
+    pub force: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Convert(Options),
+    /// Converts the directory `inputs[0]` into the directory `output`.
+    Batch(Options),
+    Help,
+}
+
+fn parse_format(format: &str) -> Result<HtmlFlavor, String> {
+    match format {
+        "html" => Ok(HtmlFlavor::Html5),
+        "xhtml" => Ok(HtmlFlavor::Xhtml),
+        _ => Err(format!(
+            "unknown format `{}`, expected html or xhtml",
+            format
+        )),
+    }
+}
+
+pub fn parse_args(args: Vec<String>) -> Result<Command, String> {
+    let mut options = Options {
+        inputs: vec![],
+        output: None,
+        flavor: HtmlFlavor::Html5,
+        standalone: false,
+        source_positions: false,
+        force: false,
+    };
+    let mut batch = None;
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        // `--name=value` is the same as `--name value`.
+        let (name, inline_value) = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => (name.to_string(), Some(value)),
+            _ => (arg.clone(), None),
+        };
+        let mut value = |name: &str| {
+            inline_value
+                .map(str::to_string)
+                .or_else(|| args.next())
+                .ok_or_else(|| format!("{} needs a value", name))
+        };
+
+        match name.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-o" | "--output" => options.output = Some(value(&name)?),
+            "-f" | "--format" => options.flavor = parse_format(&value(&name)?)?,
+            "-s" | "--standalone" => options.standalone = true,
+            "--sourcepos" => options.source_positions = true,
+            "--force" => options.force = true,
+            "-b" | "--batch" => batch = Some(value(&name)?),
+            "--" => {
+                options.inputs.extend(args);
+                break;
+            }
+            _ if arg.starts_with('-') && arg != "-" => {
+                return Err(format!("unknown option `{}`", arg));
+            }
+            _ => options.inputs.push(arg),
+        }
+    }
+
+    if let Some(input_dir) = batch {
+        if !options.inputs.is_empty() {
+            return Err("--batch takes a directory, not FILE arguments".to_string());
+        }
+        if options.output.is_none() {
+            return Err("--batch needs an output directory given with -o".to_string());
+        }
+        options.inputs.push(input_dir);
+        return Ok(Command::Batch(options));
+    }
+    if options.force {
+        return Err("--force only applies to --batch".to_string());
+    }
+    if options.inputs.is_empty() {
+        options.inputs.push("-".to_string());
+    }
+    Ok(Command::Convert(options))
+}
+
+fn read_input(path: &str) -> Result<String, String> {
+    if path == "-" {
+        let mut markdown = String::new();
+        io::stdin()
+            .read_to_string(&mut markdown)
+            .map_err(|error| format!("standard input: {}", error))?;
+        return Ok(markdown);
+    }
+    fs::read_to_string(path).map_err(|error| format!("{}: {}", path, error))
+}
+
+/// The document's first header, or else the name of the first input file.
+pub fn document_title(document: &Document, inputs: &[String]) -> String {
+    match document.outline.0.first() {
+        Some(heading) => heading.text.clone(),
+        None => inputs
+            .iter()
+            .find(|input| *input != "-")
+            .and_then(|input| Path::new(input).file_stem())
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_else(|| "Document".to_string()),
+    }
+}
+
+/// Wraps rendered HTML in a complete document with a doctype, a title and a
+/// `utf-8` charset.
+pub fn standalone_html(body: &str, title: &str, flavor: HtmlFlavor) -> String {
+    let (html_tag, meta_tag) = match flavor {
+        HtmlFlavor::Html5 => ("<html>", r#"<meta charset="utf-8">"#),
+        HtmlFlavor::Xhtml => (
+            r#"<html xmlns="http://www.w3.org/1999/xhtml">"#,
+            r#"<meta charset="utf-8"/>"#,
+        ),
+    };
+    format!(
+        "<!DOCTYPE html>\n{}\n<head>\n{}\n<title>{}</title>\n</head>\n<body>\n{}\n</body>\n</html>\n",
+        html_tag,
+        meta_tag,
+        escape_html(title),
+        body
+    )
+}
+
+/// Renders a parsed document as the options ask, ending with a newline.
+/// `inputs` are the files it was read from, used for a standalone title.
+pub fn render(document: &Document, inputs: &[String], options: &Options) -> String {
+    let render_options = RenderOptions {
+        flavor: options.flavor,
+        source_positions: options.source_positions,
+    };
+    let body = md_parser::render_html_with_options(document, render_options);
+    if options.standalone {
+        let title = document_title(document, inputs);
+        standalone_html(&body, &title, options.flavor)
+    } else {
+        body + "\n"
+    }
+}
+
+fn convert(options: &Options) -> Result<(), String> {
+    let inputs = options
+        .inputs
+        .iter()
+        .map(|input| read_input(input))
+        .collect::<Result<Vec<String>, String>>()?;
+    let document = md_parser::parse(&inputs.join("\n"));
+    let html = render(&document, &options.inputs, options);
+
+    match &options.output {
+        Some(path) => fs::write(path, html).map_err(|error| format!("{}: {}", path, error)),
+        None => io::stdout()
+            .lock()
+            .write_all(html.as_bytes())
+            .map_err(|error| format!("standard output: {}", error)),
+    }
+}
+
+/// Runs the command line and returns the process exit code: 0 on success, 1
+/// when a file cannot be read or written, 2 for bad arguments.
+pub fn run(args: Vec<String>) -> ExitCode {
+    match parse_args(args) {
+        Ok(Command::Help) => {
+            print!("{}", USAGE);
+            ExitCode::SUCCESS
+        }
+        Ok(Command::Convert(options)) => match convert(&options) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(message) => {
+                eprintln!("md_parser: {}", message);
+                ExitCode::FAILURE
+            }
+        },
+        Ok(Command::Batch(options)) => match batch::convert_tree(&options) {
+            Ok(summary) => {
+                eprintln!("md_parser: {}", summary);
+                ExitCode::SUCCESS
+            }
+            Err(message) => {
+                eprintln!("md_parser: {}", message);
+                ExitCode::FAILURE
+            }
+        },
+        Err(message) => {
+            eprintln!("md_parser: {}\n\n{}", message, USAGE);
+            ExitCode::from(USAGE_ERROR)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use md_parser::HtmlFlavor;
+
+    use crate::cli::{parse_args, standalone_html, Command, Options};
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn arguments() {
+        assert_eq!(
+            parse_args(args(&[])),
+            Ok(Command::Convert(Options {
+                inputs: args(&["-"]),
+                output: None,
+                flavor: HtmlFlavor::Html5,
+                standalone: false,
+                source_positions: false,
+                force: false,
+            }))
+        );
+        assert_eq!(
+            parse_args(args(&[
+                "a.md",
+                "-o",
+                "out.html",
+                "--format=xhtml",
+                "-s",
+                "--sourcepos",
+                "--",
+                "-b.md",
+            ])),
+            Ok(Command::Convert(Options {
+                inputs: args(&["a.md", "-b.md"]),
+                output: Some("out.html".to_string()),
+                flavor: HtmlFlavor::Xhtml,
+                standalone: true,
+                source_positions: true,
+                force: false,
+            }))
+        );
+        assert_eq!(parse_args(args(&["a.md", "--help"])), Ok(Command::Help));
+        assert_eq!(
+            parse_args(args(&["--batch", "docs", "-o", "site", "-s", "--force"])),
+            Ok(Command::Batch(Options {
+                inputs: args(&["docs"]),
+                output: Some("site".to_string()),
+                flavor: HtmlFlavor::Html5,
+                standalone: true,
+                source_positions: false,
+                force: true,
+            }))
+        );
+    }
+
+    #[test]
+    fn bad_arguments() {
+        assert_eq!(
+            parse_args(args(&["--format", "pdf"])),
+            Err("unknown format `pdf`, expected html or xhtml".to_string())
+        );
+        assert_eq!(
+            parse_args(args(&["-o"])),
+            Err("-o needs a value".to_string())
+        );
+        assert_eq!(
+            parse_args(args(&["--verbose"])),
+            Err("unknown option `--verbose`".to_string())
+        );
+        assert_eq!(
+            parse_args(args(&["--batch", "docs"])),
+            Err("--batch needs an output directory given with -o".to_string())
+        );
+        assert_eq!(
+            parse_args(args(&["--batch", "docs", "-o", "site", "a.md"])),
+            Err("--batch takes a directory, not FILE arguments".to_string())
+        );
+        assert_eq!(
+            parse_args(args(&["a.md", "--force"])),
+            Err("--force only applies to --batch".to_string())
+        );
+    }
+
+    #[test]
+    fn standalone() {
+        assert_eq!(
+            standalone_html("<p>hi</p>", "A & B", HtmlFlavor::Html5),
+            concat!(
+                "<!DOCTYPE html>\n<html>\n<head>\n",
+                "<meta charset=\"utf-8\">\n<title>A &amp; B</title>\n",
+                "</head>\n<body>\n<p>hi</p>\n</body>\n</html>\n",
+            )
+        );
+    }
+}
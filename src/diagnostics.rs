@@ -0,0 +1,75 @@
+use std::fmt::{self, Display};
+
+/// What a `Diagnostic` warns about.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiagnosticKind {
+    /// Seven or more `#`s, which is not a header.
+    HeaderTooDeep,
+    /// `#`s directly followed by text, as in `#Title`.
+    MissingHeaderSpace,
+    /// A header indented far enough to be read as text.
+    IndentedHeader,
+    /// A code fence without a closing fence, which swallows the rest of the
+    /// document.
+    UnclosedCodeFence,
+}
+
+impl DiagnosticKind {
+    pub fn message(&self) -> &'static str {
+        match self {
+            DiagnosticKind::HeaderTooDeep => "header level too deep, rendered as paragraph",
+            DiagnosticKind::MissingHeaderSpace => "`#` without space, treated as text",
+            DiagnosticKind::IndentedHeader => "indented header, treated as text",
+            DiagnosticKind::UnclosedCodeFence => {
+                "code fence is never closed, the rest of the document is code"
+            }
+        }
+    }
+}
+
+/// A warning about Markdown that parsed, but probably not the way its author
+/// meant it to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// 1-based line number.
+    pub line: usize,
+    /// 1-based column, counted in characters.
+    pub column: usize,
+    pub kind: DiagnosticKind,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new(kind: DiagnosticKind, line: usize, column: usize) -> Diagnostic {
+        Diagnostic {
+            line,
+            column,
+            kind,
+            message: kind.message().to_string(),
+        }
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: warning: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diagnostics::{Diagnostic, DiagnosticKind};
+
+    #[test]
+    fn display() {
+        let diagnostic = Diagnostic::new(DiagnosticKind::MissingHeaderSpace, 3, 1);
+        assert_eq!(
+            format!("{}", diagnostic),
+            "3:1: warning: `#` without space, treated as text"
+        );
+    }
+}
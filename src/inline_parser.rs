@@ -0,0 +1,976 @@
+use std::collections::HashMap;
+
+use crate::node::{Attributes, ClassList, Content, Node, Tag};
+
+/// How many characters a link label, destination or title may span. Scans
+/// for their closing character stop here, so text full of unclosed `[`, `(`
+/// or `"` stays linear to parse.
+const LINK_SCAN_LIMIT: usize = 1000;
+
+/// How deeply emphasis may nest. Rendering walks the tree recursively, so
+/// without a limit a long enough run of `*a ` could overflow the stack.
+const MAX_INLINE_DEPTH: usize = 100;
+
+/// A run of `*`, `_` or `~` characters that may open or close emphasis.
+struct Delimiter {
+    marker: char,
+    count: usize,
+    original_count: usize,
+    can_open: bool,
+    can_close: bool,
+}
+
+/// A piece of a line after scanning, before emphasis has been resolved.
+enum Item {
+    Text(String),
+    Inline(Content),
+    Delimiter(Delimiter),
+    /// An unmatched `[` or `![`.
+    Bracket {
+        image: bool,
+        start: usize,
+    },
+}
+
+/// The `[` and `![` items still waiting for a `]`, as indices into the item
+/// list. Once a link closes, every `[` below it is deactivated, because links
+/// may not contain other links; `active_from` is how far down the stack `[`
+/// brackets are still active.
+#[derive(Default)]
+struct BracketStack {
+    openers: Vec<usize>,
+    active_from: usize,
+}
+
+/// The target of a `[label]: destination "title"` definition.
+#[derive(Clone)]
+pub struct LinkReference {
+    pub destination: String,
+    pub title: Option<String>,
+}
+
+/// Link reference definitions collected from a whole document, keyed by their
+/// normalized label.
+#[derive(Default)]
+pub struct ReferenceMap(HashMap<String, LinkReference>);
+
+impl ReferenceMap {
+    /// Labels match case-insensitively, with runs of whitespace collapsed.
+    fn normalize_label(label: &str) -> String {
+        label
+            .split_whitespace()
+            .collect::<Vec<&str>>()
+            .join(" ")
+            .to_lowercase()
+    }
+
+    /// Adds a definition. As in CommonMark, the first definition of a label
+    /// wins.
+    pub fn insert(&mut self, label: &str, reference: LinkReference) {
+        self.0
+            .entry(ReferenceMap::normalize_label(label))
+            .or_insert(reference);
+    }
+
+    pub fn get(&self, label: &str) -> Option<&LinkReference> {
+        self.0.get(&ReferenceMap::normalize_label(label))
+    }
+}
+
+/// The `(destination "title")` part of an inline link, and the index just
+/// past its closing parenthesis.
+struct LinkTarget {
+    destination: String,
+    title: Option<String>,
+    end: usize,
+}
+
+fn is_punctuation(c: char) -> bool {
+    c.is_ascii_punctuation() || (!c.is_ascii() && !c.is_alphanumeric() && !c.is_whitespace())
+}
+
+fn inline_node(tag_name: Tag, content: Vec<Content>) -> Content {
+    Content::InnerContent(Node {
+        class_list: ClassList(vec![]),
+        content,
+        id: "".to_string(),
+        source_span: None,
+        attributes: Attributes(vec![]),
+        tag_name,
+    })
+}
+
+/// Builds a delimiter run following the CommonMark left- and right-flanking
+/// rules, so that intraword underscores such as `snake_case` stay literal.
+fn delimiter(marker: char, count: usize, before: Option<char>, after: Option<char>) -> Delimiter {
+    let before_space = before.is_none_or(char::is_whitespace);
+    let after_space = after.is_none_or(char::is_whitespace);
+    let before_punct = before.is_some_and(is_punctuation);
+    let after_punct = after.is_some_and(is_punctuation);
+
+    let left_flanking = !after_space && (!after_punct || before_space || before_punct);
+    let right_flanking = !before_space && (!before_punct || after_space || after_punct);
+
+    let (can_open, can_close) = match marker {
+        '_' => (
+            left_flanking && (!right_flanking || before_punct),
+            right_flanking && (!left_flanking || after_punct),
+        ),
+        '~' => (left_flanking && count <= 2, right_flanking && count <= 2),
+        _ => (left_flanking, right_flanking),
+    };
+
+    Delimiter {
+        marker,
+        count,
+        original_count: count,
+        can_open,
+        can_close,
+    }
+}
+
+/// Finds the closing backtick run of exactly `length` backticks, returning the
+/// index of its first backtick.
+fn find_code_span_end(chars: &[char], start: usize, length: usize) -> Option<usize> {
+    let mut index = start;
+    while index < chars.len() {
+        if chars[index] != '`' {
+            index += 1;
+            continue;
+        }
+        let run = chars[index..].iter().take_while(|&&c| c == '`').count();
+        if run == length {
+            return Some(index);
+        }
+        index += run;
+    }
+    None
+}
+
+fn code_span(chars: &[char]) -> Content {
+    let text: String = chars.iter().collect();
+    let text = text.replace('\n', " ");
+    let text = if text.len() > 2
+        && text.starts_with(' ')
+        && text.ends_with(' ')
+        && !text.trim().is_empty()
+    {
+        text[1..text.len() - 1].to_string()
+    } else {
+        text
+    };
+    inline_node(Tag::Code, vec![Content::InnerText(text)])
+}
+
+fn unescape(chars: &[char]) -> String {
+    let mut text = String::new();
+    let mut index = 0;
+    while index < chars.len() {
+        if chars[index] == '\\' && chars.get(index + 1).is_some_and(char::is_ascii_punctuation) {
+            index += 1;
+        }
+        text.push(chars[index]);
+        index += 1;
+    }
+    text
+}
+
+fn skip_whitespace(chars: &[char], mut index: usize) -> usize {
+    while index < chars.len() && chars[index].is_whitespace() {
+        index += 1;
+    }
+    index
+}
+
+/// Parses a link destination, either `<wrapped in angle brackets>` or a run of
+/// non-space characters with balanced parentheses.
+fn parse_link_destination(chars: &[char], start: usize) -> Option<(String, usize)> {
+    let limit = chars.len().min(start + LINK_SCAN_LIMIT);
+    if chars.get(start) == Some(&'<') {
+        let mut index = start + 1;
+        while index < limit {
+            match chars[index] {
+                '>' => return Some((unescape(&chars[start + 1..index]), index + 1)),
+                '<' | '\n' => return None,
+                '\\' => index += 2,
+                _ => index += 1,
+            }
+        }
+        return None;
+    }
+
+    let mut depth = 0;
+    let mut index = start;
+    while index < limit {
+        match chars[index] {
+            '\\' if chars.get(index + 1).is_some_and(char::is_ascii_punctuation) => index += 1,
+            '(' => depth += 1,
+            ')' if depth == 0 => break,
+            ')' => depth -= 1,
+            c if c.is_whitespace() || c.is_control() => break,
+            _ => {}
+        }
+        index += 1;
+    }
+    // A destination running into the limit is too long, not cut short.
+    if depth != 0 || (index >= limit && limit < chars.len()) {
+        return None;
+    }
+    Some((unescape(&chars[start..index]), index))
+}
+
+/// Parses a link title in double quotes, single quotes or parentheses.
+fn parse_link_title(chars: &[char], start: usize) -> Option<(String, usize)> {
+    let close = match chars.get(start)? {
+        '"' => '"',
+        '\'' => '\'',
+        '(' => ')',
+        _ => return None,
+    };
+    let limit = chars.len().min(start + LINK_SCAN_LIMIT);
+    let mut index = start + 1;
+    while index < limit {
+        match chars[index] {
+            '\\' => index += 2,
+            c if c == close => return Some((unescape(&chars[start + 1..index]), index + 1)),
+            '(' if close == ')' => return None,
+            _ => index += 1,
+        }
+    }
+    None
+}
+
+/// Parses the `(destination "title")` that follows the `]` of an inline link.
+fn parse_link_target(chars: &[char], start: usize) -> Option<LinkTarget> {
+    if chars.get(start) != Some(&'(') {
+        return None;
+    }
+    let mut index = skip_whitespace(chars, start + 1);
+    let (destination, after_destination) = match chars.get(index) {
+        Some(')') => (String::new(), index),
+        _ => parse_link_destination(chars, index)?,
+    };
+    index = skip_whitespace(chars, after_destination);
+
+    let mut title = None;
+    if index > after_destination {
+        if let Some((parsed, after_title)) = parse_link_title(chars, index) {
+            title = Some(parsed);
+            index = skip_whitespace(chars, after_title);
+        }
+    }
+
+    if chars.get(index) != Some(&')') {
+        return None;
+    }
+    Some(LinkTarget {
+        destination,
+        title,
+        end: index + 1,
+    })
+}
+
+/// Parses a `[label]` starting at the `[`, returning the raw label text and
+/// the index just past the `]`. Labels may not contain unescaped brackets.
+fn parse_link_label(chars: &[char], start: usize) -> Option<(String, usize)> {
+    if chars.get(start) != Some(&'[') {
+        return None;
+    }
+    let mut index = start + 1;
+    while index < chars.len() && index - start <= LINK_SCAN_LIMIT {
+        match chars[index] {
+            '\\' => index += 2,
+            '[' => return None,
+            ']' => return Some((chars[start + 1..index].iter().collect(), index + 1)),
+            _ => index += 1,
+        }
+    }
+    None
+}
+
+/// Parses a link reference definition line such as
+/// `[label]: https://example.com "Title"`.
+pub fn parse_link_definition(line: &str) -> Option<(String, LinkReference)> {
+    let indent = line.len() - line.trim_start_matches(' ').len();
+    if indent > 3 {
+        return None;
+    }
+    let chars: Vec<char> = line.trim().chars().collect();
+    let (label, after_label) = parse_link_label(&chars, 0)?;
+    if label.trim().is_empty() || chars.get(after_label) != Some(&':') {
+        return None;
+    }
+
+    let start = skip_whitespace(&chars, after_label + 1);
+    if start == chars.len() {
+        return None;
+    }
+    let (destination, after_destination) = parse_link_destination(&chars, start)?;
+    if destination.is_empty() && chars[start] != '<' {
+        return None;
+    }
+
+    let index = skip_whitespace(&chars, after_destination);
+    if index == chars.len() {
+        let reference = LinkReference {
+            destination,
+            title: None,
+        };
+        return Some((label, reference));
+    }
+    if index == after_destination {
+        return None;
+    }
+    let (title, after_title) = parse_link_title(&chars, index)?;
+    if skip_whitespace(&chars, after_title) != chars.len() {
+        return None;
+    }
+    let reference = LinkReference {
+        destination,
+        title: Some(title),
+    };
+    Some((label, reference))
+}
+
+/// Parses an autolink such as `<https://example.com>` or `<me@example.com>`,
+/// returning the link and the index just past the closing `>`.
+fn parse_autolink(chars: &[char], start: usize) -> Option<(Content, usize)> {
+    // Whitespace, control characters and `<` can't appear in an autolink, so
+    // the scan for `>` stops at the first one.
+    let length = chars[start + 1..]
+        .iter()
+        .position(|&c| c == '>' || c.is_whitespace() || c.is_control() || c == '<')?;
+    if chars[start + 1 + length] != '>' {
+        return None;
+    }
+    let text: String = chars[start + 1..start + 1 + length].iter().collect();
+
+    let href = match text.split_once(':') {
+        Some((scheme, _))
+            if (2..=32).contains(&scheme.len())
+                && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '.' | '-')) =>
+        {
+            text.clone()
+        }
+        _ => match text.split_once('@') {
+            Some((local, domain))
+                if !local.is_empty() && domain.contains('.') && !domain.contains('@') =>
+            {
+                format!("mailto:{}", text)
+            }
+            _ => return None,
+        },
+    };
+
+    let link = link_node(href, None, vec![Content::InnerText(text)]);
+    Some((link, start + length + 2))
+}
+
+fn link_node(href: String, title: Option<String>, content: Vec<Content>) -> Content {
+    let mut attributes = vec![("href".to_string(), href)];
+    if let Some(title) = title {
+        attributes.push(("title".to_string(), title));
+    }
+    Content::InnerContent(Node {
+        class_list: ClassList(vec![]),
+        content,
+        id: "".to_string(),
+        source_span: None,
+        attributes: Attributes(attributes),
+        tag_name: Tag::A,
+    })
+}
+
+fn image_node(src: String, title: Option<String>, content: Vec<Content>) -> Content {
+    let mut attributes = vec![
+        ("src".to_string(), src),
+        ("alt".to_string(), plain_text(&content)),
+    ];
+    if let Some(title) = title {
+        attributes.push(("title".to_string(), title));
+    }
+    Content::InnerContent(Node {
+        class_list: ClassList(vec![]),
+        content: vec![],
+        id: "".to_string(),
+        source_span: None,
+        attributes: Attributes(attributes),
+        tag_name: Tag::Img,
+    })
+}
+
+/// The text of some content with all markup removed, as used for image alt
+/// text.
+pub fn plain_text(content: &[Content]) -> String {
+    content
+        .iter()
+        .map(|content| match content {
+            Content::InnerText(text) => text.clone(),
+            Content::RawHtml(_) => "".to_string(),
+            Content::InnerContent(node) => match node.tag_name {
+                Tag::Img => node
+                    .attributes
+                    .0
+                    .iter()
+                    .find(|(label, _)| label == "alt")
+                    .map(|(_, alt)| alt.clone())
+                    .unwrap_or_default(),
+                _ => plain_text(&node.content),
+            },
+        })
+        .collect()
+}
+
+/// Finds what a `]` links to: an inline `(destination "title")`, or a full
+/// `[text][label]`, collapsed `[label][]` or shortcut `[label]` reference.
+/// Returns the target and the index just past the link.
+fn find_link_target(
+    chars: &[char],
+    start: usize,
+    index: usize,
+    references: &ReferenceMap,
+) -> Option<(LinkReference, usize)> {
+    if let Some(target) = parse_link_target(chars, index + 1) {
+        let reference = LinkReference {
+            destination: target.destination,
+            title: target.title,
+        };
+        return Some((reference, target.end));
+    }
+
+    // Collapsed and shortcut references use the link text as their label,
+    // which is no longer than any other label.
+    let text = || chars[start..index].iter().collect::<String>();
+    let (label, end) = match parse_link_label(chars, index + 1) {
+        Some((label, end)) if !label.trim().is_empty() => (label, end),
+        _ if index - start > LINK_SCAN_LIMIT => return None,
+        Some((_, end)) => (text(), end),
+        None => (text(), index + 1),
+    };
+    references
+        .get(&label)
+        .map(|reference| (reference.clone(), end))
+}
+
+/// Handles a `]`: if it closes an active bracket that has a link target,
+/// everything since the bracket becomes the link text. Returns the index to
+/// continue scanning from.
+fn close_bracket(
+    items: &mut Vec<Item>,
+    brackets: &mut BracketStack,
+    chars: &[char],
+    index: usize,
+    references: &ReferenceMap,
+) -> usize {
+    let Some(opener_index) = brackets.openers.pop() else {
+        push_text(items, "]");
+        return index + 1;
+    };
+    let Item::Bracket { image, start } = items[opener_index] else {
+        unreachable!()
+    };
+    let active = image || brackets.openers.len() >= brackets.active_from;
+    brackets.active_from = brackets.active_from.min(brackets.openers.len());
+
+    let (target, end) = match find_link_target(chars, start, index, references) {
+        Some(found) if active => found,
+        _ => {
+            items[opener_index] = Item::Text(if image { "![" } else { "[" }.to_string());
+            push_text(items, "]");
+            return index + 1;
+        }
+    };
+
+    let inner: Vec<Item> = items.drain(opener_index + 1..).collect();
+    items.pop();
+    let content = into_content(process_emphasis(inner));
+    if image {
+        items.push(Item::Inline(image_node(
+            target.destination,
+            target.title,
+            content,
+        )));
+    } else {
+        items.push(Item::Inline(link_node(
+            target.destination,
+            target.title,
+            content,
+        )));
+        brackets.active_from = brackets.openers.len();
+    }
+    end
+}
+
+fn push_text(items: &mut Vec<Item>, text: &str) {
+    match items.last_mut() {
+        Some(Item::Text(last)) => last.push_str(text),
+        _ => items.push(Item::Text(text.to_string())),
+    }
+}
+
+fn scan(text: &str, references: &ReferenceMap) -> Vec<Item> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut items = vec![];
+    let mut brackets = BracketStack::default();
+    let mut index = 0;
+
+    while index < chars.len() {
+        let c = chars[index];
+        match c {
+            '\\' if chars.get(index + 1) == Some(&'\n') => {
+                items.push(Item::Inline(inline_node(Tag::Br, vec![])));
+                push_text(&mut items, "\n");
+                index += 2;
+            }
+            '\n' => {
+                // Two or more spaces before a newline make a hard break; any
+                // other trailing spaces are dropped from a soft break.
+                let mut hard_break = false;
+                if let Some(Item::Text(last)) = items.last_mut() {
+                    hard_break = last.ends_with("  ");
+                    last.truncate(last.trim_end_matches(' ').len());
+                }
+                if hard_break {
+                    items.push(Item::Inline(inline_node(Tag::Br, vec![])));
+                }
+                push_text(&mut items, "\n");
+                index += 1;
+            }
+            '\\' if chars.get(index + 1).is_some_and(char::is_ascii_punctuation) => {
+                push_text(&mut items, &chars[index + 1].to_string());
+                index += 2;
+            }
+            '`' => {
+                let length = chars[index..].iter().take_while(|&&c| c == '`').count();
+                match find_code_span_end(&chars, index + length, length) {
+                    Some(end) => {
+                        items.push(Item::Inline(code_span(&chars[index + length..end])));
+                        index = end + length;
+                    }
+                    None => {
+                        push_text(&mut items, &"`".repeat(length));
+                        index += length;
+                    }
+                }
+            }
+            '!' if chars.get(index + 1) == Some(&'[') => {
+                brackets.openers.push(items.len());
+                items.push(Item::Bracket {
+                    image: true,
+                    start: index + 2,
+                });
+                index += 2;
+            }
+            '[' => {
+                brackets.openers.push(items.len());
+                items.push(Item::Bracket {
+                    image: false,
+                    start: index + 1,
+                });
+                index += 1;
+            }
+            ']' => index = close_bracket(&mut items, &mut brackets, &chars, index, references),
+            '<' => match parse_autolink(&chars, index) {
+                Some((link, end)) => {
+                    items.push(Item::Inline(link));
+                    index = end;
+                }
+                None => {
+                    push_text(&mut items, "<");
+                    index += 1;
+                }
+            },
+            '*' | '_' | '~' => {
+                let count = chars[index..].iter().take_while(|&&x| x == c).count();
+                let before = index.checked_sub(1).map(|i| chars[i]);
+                let after = chars.get(index + count).copied();
+                items.push(Item::Delimiter(delimiter(c, count, before, after)));
+                index += count;
+            }
+            _ => {
+                push_text(&mut items, &c.to_string());
+                index += 1;
+            }
+        }
+    }
+
+    items
+}
+
+/// Turns whatever is left of the item list into content, with unmatched
+/// delimiters falling back to literal text.
+fn into_content(items: Vec<Item>) -> Vec<Content> {
+    let mut content = vec![];
+    for item in items {
+        let text = match item {
+            Item::Inline(inline) => {
+                content.push(inline);
+                continue;
+            }
+            Item::Text(text) => text,
+            Item::Delimiter(delimiter) => delimiter.marker.to_string().repeat(delimiter.count),
+            Item::Bracket { image: true, .. } => "![".to_string(),
+            Item::Bracket { image: false, .. } => "[".to_string(),
+        };
+        match content.last_mut() {
+            Some(Content::InnerText(last)) => last.push_str(&text),
+            _ => content.push(Content::InnerText(text)),
+        }
+    }
+    content
+}
+
+/// Whether `opener` can pair with `closer`.
+fn pairs_with(opener: &Delimiter, closer: &Delimiter) -> bool {
+    if opener.marker != closer.marker {
+        return false;
+    }
+    if closer.marker == '~' {
+        return opener.count == closer.count;
+    }
+    // The "rule of three" keeps `*foo**bar*` from pairing the wrong runs.
+    let either_both =
+        (opener.can_open && opener.can_close) || (closer.can_open && closer.can_close);
+    !either_both
+        || !(opener.original_count + closer.original_count).is_multiple_of(3)
+        || (opener.original_count.is_multiple_of(3) && closer.original_count.is_multiple_of(3))
+}
+
+/// How many levels of inline markup `content` is wrapped in.
+fn content_depth(content: &Content) -> usize {
+    match content {
+        Content::InnerContent(node) => {
+            1 + node.content.iter().map(content_depth).max().unwrap_or(0)
+        }
+        _ => 0,
+    }
+}
+
+/// Pairs up delimiter runs from left to right, wrapping everything between an
+/// opener and its closer in `<em>`, `<strong>` or `<del>`.
+///
+/// As in CommonMark, `openers` is a stack of the delimiters that may still
+/// open, and `openers_bottom` remembers, for each kind of closer, how far
+/// down that stack a search has already failed, so no opener is looked at
+/// twice by the same kind of closer. Runs that would nest deeper than
+/// `MAX_INLINE_DEPTH` stay literal.
+fn process_emphasis(items: Vec<Item>) -> Vec<Item> {
+    let mut output: Vec<Item> = vec![];
+    let mut depths: Vec<usize> = vec![];
+    let mut openers: Vec<usize> = vec![];
+    let mut openers_bottom: HashMap<(char, bool, usize), usize> = HashMap::new();
+
+    for item in items {
+        let mut closer = match item {
+            Item::Delimiter(closer) if closer.can_close => closer,
+            item => {
+                if matches!(&item, Item::Delimiter(opener) if opener.can_open) {
+                    openers.push(output.len());
+                }
+                depths.push(match &item {
+                    Item::Inline(content) => content_depth(content),
+                    _ => 0,
+                });
+                output.push(item);
+                continue;
+            }
+        };
+        let key = (closer.marker, closer.can_open, closer.original_count % 3);
+
+        while closer.count > 0 {
+            let bottom = openers_bottom.get(&key).copied().unwrap_or(0);
+            let found = (bottom..openers.len()).rev().find(|&position| {
+                matches!(&output[openers[position]], Item::Delimiter(opener) if pairs_with(opener, &closer))
+            });
+            let Some(position) = found else {
+                openers_bottom.insert(key, openers.len());
+                break;
+            };
+
+            let opener_index = openers[position];
+            let depth = 1 + depths[opener_index + 1..].iter().max().unwrap_or(&0);
+            if depth > MAX_INLINE_DEPTH {
+                // Every opener at or below this one would wrap the same deep
+                // content, so none of them can be used again.
+                openers.drain(..=position);
+                for bottom in openers_bottom.values_mut() {
+                    *bottom = bottom.saturating_sub(position + 1);
+                }
+                openers_bottom.insert(key, openers.len());
+                break;
+            }
+
+            // Delimiters between the opener and the closer can no longer
+            // open anything.
+            openers.truncate(position + 1);
+            let Item::Delimiter(opener) = &mut output[opener_index] else {
+                unreachable!()
+            };
+            let (used, tag_name) = match closer.marker {
+                '~' => (closer.count, Tag::Del),
+                _ if opener.count >= 2 && closer.count >= 2 => (2, Tag::Strong),
+                _ => (1, Tag::Em),
+            };
+            opener.count -= used;
+            closer.count -= used;
+            let opener_used_up = opener.count == 0;
+
+            let inner: Vec<Item> = output.drain(opener_index + 1..).collect();
+            depths.truncate(opener_index + 1);
+            output.push(Item::Inline(inline_node(tag_name, into_content(inner))));
+            depths.push(depth);
+            if opener_used_up {
+                output.remove(opener_index);
+                depths.remove(opener_index);
+                openers.pop();
+            }
+            for bottom in openers_bottom.values_mut() {
+                *bottom = (*bottom).min(openers.len());
+            }
+        }
+
+        if closer.count > 0 {
+            if closer.can_open {
+                openers.push(output.len());
+            }
+            depths.push(0);
+            output.push(Item::Delimiter(closer));
+        }
+    }
+
+    output
+}
+
+/// Parses the inline Markdown of a single block (emphasis, strong emphasis,
+/// strikethrough, code spans, links, images and autolinks) into a list of
+/// content. Reference links are resolved against `references`.
+pub fn parse_inline(text: &str, references: &ReferenceMap) -> Vec<Content> {
+    into_content(process_emphasis(scan(text, references)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::inline_parser::{
+        parse_inline, parse_link_definition, ReferenceMap, MAX_INLINE_DEPTH,
+    };
+
+    fn render(text: &str) -> String {
+        render_with_references(text, &ReferenceMap::default())
+    }
+
+    fn render_with_references(text: &str, references: &ReferenceMap) -> String {
+        parse_inline(text, references)
+            .iter()
+            .map(|content| format!("{}", content))
+            .collect()
+    }
+
+    #[test]
+    fn plain_text() {
+        assert_eq!(render("just some text"), "just some text");
+        assert_eq!(render(""), "");
+    }
+
+    #[test]
+    fn emphasis_and_strong() {
+        assert_eq!(render("*em*"), "<em>em</em>");
+        assert_eq!(render("_em_"), "<em>em</em>");
+        assert_eq!(render("**strong**"), "<strong>strong</strong>");
+        assert_eq!(render("__strong__"), "<strong>strong</strong>");
+        assert_eq!(render("***both***"), "<em><strong>both</strong></em>");
+        assert_eq!(
+            render("a *nested **strong** run*"),
+            "a <em>nested <strong>strong</strong> run</em>"
+        );
+    }
+
+    #[test]
+    fn flanking_rules() {
+        assert_eq!(render("snake_case_names"), "snake_case_names");
+        assert_eq!(render("a * not em *"), "a * not em *");
+        assert_eq!(render("intra*word*em"), "intra<em>word</em>em");
+        assert_eq!(render("*foo**bar*"), "<em>foo**bar</em>");
+        assert_eq!(render("**unclosed"), "**unclosed");
+    }
+
+    #[test]
+    fn strikethrough() {
+        assert_eq!(render("~~gone~~"), "<del>gone</del>");
+        assert_eq!(render("~one~"), "<del>one</del>");
+        assert_eq!(render("~~mismatched~"), "~~mismatched~");
+    }
+
+    #[test]
+    fn code_spans() {
+        assert_eq!(render("`code`"), "<code>code</code>");
+        assert_eq!(render("`` a ` b ``"), "<code>a ` b</code>");
+        assert_eq!(render("`*not em*`"), "<code>*not em*</code>");
+        assert_eq!(render("``unclosed`"), "``unclosed`");
+    }
+
+    #[test]
+    fn inline_links() {
+        assert_eq!(
+            render("see [the docs](https://example.com)"),
+            r#"see <a href="https://example.com">the docs</a>"#
+        );
+        assert_eq!(
+            render(r#"[a *b*](/url "The Title")"#),
+            r#"<a href="/url" title="The Title">a <em>b</em></a>"#
+        );
+        assert_eq!(
+            render("[paren](</my url> 'single')"),
+            r#"<a href="/my url" title="single">paren</a>"#
+        );
+        assert_eq!(
+            render("[balanced](/a(b)c)"),
+            r#"<a href="/a(b)c">balanced</a>"#
+        );
+        assert_eq!(render("[empty]()"), r#"<a href="">empty</a>"#);
+    }
+
+    #[test]
+    fn not_links() {
+        assert_eq!(render("[just brackets]"), "[just brackets]");
+        assert_eq!(render("[space] (/url)"), "[space] (/url)");
+        assert_eq!(render("[open](/url"), "[open](/url");
+        assert_eq!(render("a ] b"), "a ] b");
+        assert_eq!(
+            render("[outer [inner](/a)](/b)"),
+            r#"[outer <a href="/a">inner</a>](/b)"#
+        );
+    }
+
+    #[test]
+    fn images() {
+        assert_eq!(
+            render("![a *cat*](/cat.png)"),
+            r#"<img src="/cat.png" alt="a cat">"#
+        );
+        assert_eq!(
+            render(r#"[![logo](/logo.png "Logo")](/home)"#),
+            r#"<a href="/home"><img src="/logo.png" alt="logo" title="Logo"></a>"#
+        );
+        assert_eq!(render("!not an image"), "!not an image");
+    }
+
+    #[test]
+    fn autolinks() {
+        assert_eq!(
+            render("<https://example.com/a?b=c>"),
+            r#"<a href="https://example.com/a?b=c">https://example.com/a?b=c</a>"#
+        );
+        assert_eq!(
+            render("<me@example.com>"),
+            r#"<a href="mailto:me@example.com">me@example.com</a>"#
+        );
+        assert_eq!(render("a <b> c"), "a &lt;b&gt; c");
+        assert_eq!(render("1 < 2"), "1 &lt; 2");
+        assert_eq!(render("<a b> <c>"), "&lt;a b&gt; &lt;c&gt;");
+    }
+
+    #[test]
+    fn unclosed_links_stay_linear() {
+        let text = "<a ".repeat(20_000);
+        assert!(render(&text) == "&lt;a ".repeat(20_000));
+
+        let text = "[a](".repeat(10_000);
+        assert!(render(&text) == text);
+
+        let long_url = format!("[a](/{})", "x".repeat(2000));
+        assert_eq!(render(&long_url), long_url);
+
+        let text = format!("{}{}", "[".repeat(20_000), "]".repeat(20_000));
+        assert!(render(&text) == text);
+
+        let text = "[a](b) ".repeat(20_000);
+        assert!(render(&text) == r#"<a href="b">a</a> "#.repeat(20_000));
+    }
+
+    #[test]
+    fn emphasis_stays_linear() {
+        let text = "a* ".repeat(20_000);
+        assert!(render(&text) == text);
+
+        // Only the innermost `MAX_INLINE_DEPTH` runs nest.
+        let text = format!("{}x{}", "*a ".repeat(20_000), " a*".repeat(20_000));
+        let expected = format!(
+            "{}{}x{}{}",
+            "*a ".repeat(20_000 - MAX_INLINE_DEPTH),
+            "<em>a ".repeat(MAX_INLINE_DEPTH),
+            " a</em>".repeat(MAX_INLINE_DEPTH),
+            " a*".repeat(20_000 - MAX_INLINE_DEPTH),
+        );
+        assert!(render(&text) == expected);
+    }
+
+    #[test]
+    fn link_definitions() {
+        let (label, reference) = parse_link_definition(r#"[Foo Bar]: /url "Title""#).unwrap();
+        assert_eq!(label, "Foo Bar");
+        assert_eq!(reference.destination, "/url");
+        assert_eq!(reference.title, Some("Title".to_string()));
+
+        let (_, reference) = parse_link_definition("   [a]: <my url>").unwrap();
+        assert_eq!(reference.destination, "my url");
+        assert_eq!(reference.title, None);
+
+        assert!(parse_link_definition("[a]:").is_none());
+        assert!(parse_link_definition("[a]: /url trailing").is_none());
+        assert!(parse_link_definition("[a]: /url 'title' trailing").is_none());
+        assert!(parse_link_definition("    [a]: /url").is_none());
+        assert!(parse_link_definition("[]: /url").is_none());
+    }
+
+    #[test]
+    fn reference_links() {
+        let mut references = ReferenceMap::default();
+        for line in [
+            "[Docs  Home]: /docs 'Docs'",
+            "[logo]: /logo.png",
+            "[logo]: /ignored",
+        ] {
+            let (label, reference) = parse_link_definition(line).unwrap();
+            references.insert(&label, reference);
+        }
+
+        assert_eq!(
+            render_with_references("[the docs][docs home]", &references),
+            r#"<a href="/docs" title="Docs">the docs</a>"#
+        );
+        assert_eq!(
+            render_with_references("[DOCS HOME][]", &references),
+            r#"<a href="/docs" title="Docs">DOCS HOME</a>"#
+        );
+        assert_eq!(
+            render_with_references("see [docs\nhome].", &references),
+            "see <a href=\"/docs\" title=\"Docs\">docs\nhome</a>."
+        );
+        assert_eq!(
+            render_with_references("![logo]", &references),
+            r#"<img src="/logo.png" alt="logo">"#
+        );
+        assert_eq!(
+            render_with_references("[missing][nowhere] [missing]", &references),
+            "[missing][nowhere] [missing]"
+        );
+    }
+
+    #[test]
+    fn line_breaks() {
+        assert_eq!(render("soft\nbreak"), "soft\nbreak");
+        assert_eq!(render("one space \nsoft"), "one space\nsoft");
+        assert_eq!(render("hard  \nbreak"), "hard<br>\nbreak");
+        assert_eq!(render("hard\\\nbreak"), "hard<br>\nbreak");
+        assert_eq!(render("*em\nacross*"), "<em>em\nacross</em>");
+    }
+
+    #[test]
+    fn backslash_escapes() {
+        assert_eq!(render(r"\*not em\*"), "*not em*");
+        assert_eq!(render(r"a \b c"), r"a \b c");
+    }
+}
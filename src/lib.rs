@@ -0,0 +1,120 @@
+//! A Markdown to HTML converter.
+//!
+//! ```
+//! let document = md_parser::parse("# Hello\n\nSome *text*");
+//! let html = md_parser::render_html(&document);
+//!
+//! assert!(html.contains(r#"<h1 id="hello" class="md-h1">Hello</h1>"#));
+//! assert!(html.contains("<em>text</em>"));
+//! ```
+
+mod diagnostics;
+mod inline_parser;
+mod markdown_parser;
+mod node;
+mod sanitizer;
+mod toc;
+
+use markdown_parser::{collect_tasks, MarkdownLine};
+
+pub use diagnostics::{Diagnostic, DiagnosticKind};
+pub use markdown_parser::Task;
+pub use node::{
+    escape_html, Attributes, ClassList, Content, HtmlFlavor, Node, NodeList, RenderOptions,
+    SourceSpan, Tag, UnknownTag,
+};
+pub use sanitizer::SanitizePolicy;
+pub use toc::{Heading, Outline};
+
+/// A parsed Markdown document.
+pub struct Document {
+    /// The top-level blocks of the document, in order.
+    pub nodes: NodeList,
+    /// Every header of the document, for building a table of contents.
+    pub outline: Outline,
+    /// Warnings about Markdown that parsed, but probably not the way its
+    /// author meant it to.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Document {
+    /// Passes the document's nodes through `policy`, for Markdown that
+    /// cannot be trusted.
+    pub fn sanitize(self, policy: &SanitizePolicy) -> Document {
+        Document {
+            nodes: policy.sanitize(self.nodes),
+            ..self
+        }
+    }
+
+    /// Every task list item in the document, such as `- [ ] write docs`, in
+    /// document order.
+    pub fn tasks(&self) -> Vec<Task> {
+        collect_tasks(&self.nodes)
+    }
+}
+
+/// Parses Markdown into a `Document`. Parsing never fails: Markdown that
+/// doesn't mean anything special is kept as text, and anything that looks
+/// like a mistake is reported in the document's `diagnostics`.
+pub fn parse(markdown: &str) -> Document {
+    let (nodes, outline, diagnostics) = MarkdownLine::parse_document(markdown);
+    Document {
+        nodes,
+        outline,
+        diagnostics,
+    }
+}
+
+/// Renders a document as HTML5, one top-level block per line.
+pub fn render_html(document: &Document) -> String {
+    render_html_with_options(document, RenderOptions::default())
+}
+
+/// Like `render_html`, with a choice of HTML flavor and `data-sourcepos`
+/// attributes.
+pub fn render_html_with_options(document: &Document, options: RenderOptions) -> String {
+    document.nodes.to_html(options)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse, render_html, render_html_with_options};
+    use crate::{DiagnosticKind, RenderOptions, SanitizePolicy};
+
+    #[test]
+    fn parse_and_render() {
+        let document = parse("## Intro\n#Oops\n- [x] done");
+
+        assert_eq!(
+            render_html(&document),
+            concat!(
+                r#"<h2 id="intro" class="md-h2">Intro</h2>"#,
+                "\n",
+                r#"<p class="md-p">#Oops</p>"#,
+                "\n",
+                r#"<ul class="md-ul"><li class="md-li md-task"><input type="checkbox" checked="" disabled=""> done</li></ul>"#,
+            )
+        );
+        assert_eq!(document.outline.0[0].id, "intro");
+        assert_eq!(
+            document.diagnostics[0].kind,
+            DiagnosticKind::MissingHeaderSpace
+        );
+        assert_eq!(document.tasks()[0].line, 3);
+
+        let options = RenderOptions {
+            source_positions: true,
+            ..RenderOptions::default()
+        };
+        assert!(render_html_with_options(&document, options)
+            .starts_with(r#"<h2 id="intro" class="md-h2" data-sourcepos="1:1-1:8">"#));
+    }
+
+    #[test]
+    fn sanitize_document() {
+        let document =
+            parse("[x](javascript:alert(1))").sanitize(&SanitizePolicy::strict_comments());
+        assert_eq!(render_html(&document), r#"<p class="md-p"><a>x</a></p>"#);
+    }
+}
@@ -1,11 +1,7 @@
-#![allow(unused_imports, dead_code)]
-
-use core::slice;
-use std::fmt::{format, Display};
-
-use regex::Regex;
-
-use crate::node::{ClassList, Content, Node, NodeList, Tag};
+use crate::diagnostics::{Diagnostic, DiagnosticKind};
+use crate::inline_parser::{parse_inline, parse_link_definition, plain_text, ReferenceMap};
+use crate::node::{Attributes, ClassList, Content, Node, NodeList, SourceSpan, Tag};
+use crate::toc::{assign_heading_ids, Outline};
 
 #[derive(Clone)]
 pub enum MarkDownLineType {
@@ -13,116 +9,1219 @@ pub enum MarkDownLineType {
     H2,
     H3,
     H4,
+    H5,
+    H6,
     Li,
+    OrderedLi(u32),
+    CodeFence,
+    IndentedCode,
+    BlockQuote,
+    ThematicBreak,
+    LinkDefinition,
     NoTag,
     EmptyLine,
 }
 
+#[cfg(test)]
 fn split_by_newline(input: String) -> Vec<String> {
     input
-        .split("\n")
-        .filter(|&s| s.trim() != "")
+        .split('\n')
+        .filter(|&s| !s.trim().is_empty())
         .map(|s| s.trim().to_string())
         .collect()
 }
 
+/// How deeply blockquotes may nest. Each level is parsed recursively, so
+/// without a limit a single line of `>` could overflow the stack.
+const MAX_QUOTE_DEPTH: usize = 100;
+
+/// An opening or closing code fence: a run of at least three backticks or
+/// tildes, indented by no more than three spaces.
+#[derive(Clone)]
+struct Fence {
+    marker: char,
+    length: usize,
+    indent: usize,
+    info: String,
+}
+
+impl Fence {
+    fn parse(line: &str) -> Option<Fence> {
+        let indent = line.len() - line.trim_start_matches(' ').len();
+        if indent > 3 {
+            return None;
+        }
+        let rest = &line[indent..];
+        let marker = rest.chars().next()?;
+        if marker != '`' && marker != '~' {
+            return None;
+        }
+        let length = rest.len() - rest.trim_start_matches(marker).len();
+        if length < 3 {
+            return None;
+        }
+        let info = rest[length..].trim().to_string();
+        if marker == '`' && info.contains('`') {
+            return None;
+        }
+        Some(Fence {
+            marker,
+            length,
+            indent,
+            info,
+        })
+    }
+
+    fn is_closed_by(&self, line: &str) -> bool {
+        match Fence::parse(line) {
+            Some(close) => {
+                close.marker == self.marker && close.length >= self.length && close.info.is_empty()
+            }
+            None => false,
+        }
+    }
+
+    fn language(&self) -> Option<&str> {
+        self.info.split_whitespace().next()
+    }
+
+    /// Removes up to `indent` leading spaces from a line inside the fence.
+    fn strip_indent<'a>(&self, line: &'a str) -> &'a str {
+        let spaces = line.len() - line.trim_start_matches(' ').len();
+        &line[spaces.min(self.indent)..]
+    }
+}
+
+/// The width of a line's leading whitespace in columns, with tabs advancing
+/// to the next multiple of four.
+fn leading_columns(line: &str) -> usize {
+    let mut columns = 0;
+    for c in line.chars() {
+        match c {
+            ' ' => columns += 1,
+            '\t' => columns += 4 - columns % 4,
+            _ => break,
+        }
+    }
+    columns
+}
+
+/// Removes `columns` columns of leading whitespace, or all of it if there is
+/// less. A tab that straddles the cut leaves its remaining columns as spaces.
+fn strip_columns(line: &str, columns: usize) -> String {
+    let mut width = 0;
+    for (index, c) in line.char_indices() {
+        if width >= columns {
+            return " ".repeat(width - columns) + &line[index..];
+        }
+        match c {
+            ' ' => width += 1,
+            '\t' => width += 4 - width % 4,
+            _ => return line[index..].to_string(),
+        }
+    }
+    " ".repeat(width.saturating_sub(columns))
+}
+
+/// The span of a whole line, starting at byte `offset` of the document.
+fn line_span(line: &str, line_number: usize, offset: usize) -> SourceSpan {
+    suffix_span(
+        &SourceSpan {
+            start_offset: offset,
+            end_offset: offset + line.len(),
+            start_line: line_number,
+            start_column: 1,
+            end_line: line_number,
+            end_column: line.chars().count(),
+        },
+        line,
+    )
+}
+
+/// The part of a one-line span taken up by `suffix`, the end of its line
+/// (such as the content after a `>` marker), from its first non-whitespace
+/// character. A blank suffix has no characters of its own, so it gets an
+/// empty byte range on the last column of the line, or on column 1 of an
+/// empty line.
+fn suffix_span(span: &SourceSpan, suffix: &str) -> SourceSpan {
+    let trimmed = suffix.trim_start();
+    if trimmed.is_empty() {
+        let column = span.end_column.max(1);
+        return SourceSpan {
+            start_offset: span.end_offset,
+            start_column: column,
+            end_column: column,
+            ..*span
+        };
+    }
+    SourceSpan {
+        start_offset: span.end_offset - trimmed.len(),
+        start_column: span.end_column - trimmed.chars().count() + 1,
+        ..*span
+    }
+}
+
+fn is_blank(line: &str) -> bool {
+    line.trim().is_empty()
+}
+
+/// The marker of an ordered list item, such as `1.` or `3)`.
+struct OrderedMarker {
+    start: u32,
+    indent: usize,
+    width: usize,
+}
+
+impl OrderedMarker {
+    fn parse(line: &str) -> Option<OrderedMarker> {
+        let trimmed = line.trim_start();
+        let indent = line.len() - trimmed.len();
+        let digits = trimmed.len()
+            - trimmed
+                .trim_start_matches(|c: char| c.is_ascii_digit())
+                .len();
+        if digits == 0 || digits > 9 {
+            return None;
+        }
+        let rest = &trimmed[digits..];
+        if !rest.starts_with('.') && !rest.starts_with(')') {
+            return None;
+        }
+        let rest = &rest[1..];
+        if !rest.starts_with([' ', '\t']) || rest.trim().is_empty() {
+            return None;
+        }
+        Some(OrderedMarker {
+            start: trimmed[..digits].parse().ok()?,
+            indent,
+            width: digits + 1,
+        })
+    }
+
+    fn content<'a>(&self, line: &'a str) -> &'a str {
+        line[self.indent + self.width..].trim_start()
+    }
+}
+
 #[derive(Clone)]
 pub struct MarkdownLine {
     indent: i32,
     line_type: MarkDownLineType,
     content: String,
+    raw: String,
+    /// Where the line is in the document, from its first non-whitespace
+    /// character. Lines made by `parse` alone have a default span.
+    span: SourceSpan,
+}
+
+/// The alignment of a table column, set by the colons of its delimiter cell.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Alignment {
+    None,
+    Left,
+    Center,
+    Right,
+}
+
+impl Alignment {
+    fn parse(cell: &str) -> Option<Alignment> {
+        let dashes = cell.trim_start_matches(':').trim_end_matches(':');
+        if dashes.is_empty() || !dashes.chars().all(|c| c == '-') {
+            return None;
+        }
+        match (cell.starts_with(':'), cell.ends_with(':')) {
+            (true, true) => Some(Alignment::Center),
+            (true, false) => Some(Alignment::Left),
+            (false, true) => Some(Alignment::Right),
+            (false, false) => Some(Alignment::None),
+        }
+    }
+
+    fn class_list(&self) -> ClassList {
+        match self {
+            Alignment::None => ClassList(vec![]),
+            Alignment::Left => ClassList(vec!["md-align-left".to_string()]),
+            Alignment::Center => ClassList(vec!["md-align-center".to_string()]),
+            Alignment::Right => ClassList(vec!["md-align-right".to_string()]),
+        }
+    }
+}
+
+/// Splits a table row into its trimmed cells. The optional leading and
+/// trailing pipes are dropped, and `\|` is a literal pipe inside a cell.
+fn split_table_row(line: &str) -> Vec<String> {
+    let trimmed = line.trim();
+    let trimmed = trimmed.strip_prefix('|').unwrap_or(trimmed);
+    let trimmed = match trimmed.strip_suffix('|') {
+        Some(rest) if !rest.ends_with('\\') => rest,
+        _ => trimmed,
+    };
+
+    let mut cells = vec![];
+    let mut cell = String::new();
+    let mut chars = trimmed.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'|') => {
+                cell.push('|');
+                chars.next();
+            }
+            '|' => cells.push(std::mem::take(&mut cell)),
+            _ => cell.push(c),
+        }
+    }
+    cells.push(cell);
+
+    cells.iter().map(|cell| cell.trim().to_string()).collect()
+}
+
+/// The column alignments of a table delimiter row such as `| :-- | --: |`.
+fn parse_delimiter_row(line: &str) -> Option<Vec<Alignment>> {
+    if !line.contains('|') {
+        return None;
+    }
+    split_table_row(line)
+        .iter()
+        .map(|cell| Alignment::parse(cell))
+        .collect()
+}
+
+/// A task list item, such as `- [x] ship it`.
+pub struct Task {
+    pub checked: bool,
+    /// The item's text without its formatting or nested lists.
+    pub text: String,
+    /// The 1-based line number of the item.
+    pub line: usize,
+}
+
+fn visit_task_items(node: &Node, tasks: &mut Vec<Task>) {
+    if node.tag_name == Tag::Li && node.class_list.0.iter().any(|class| class == "md-task") {
+        let checked = node.content.iter().any(|content| match content {
+            Content::InnerContent(input) if input.tag_name == Tag::Input => input
+                .attributes
+                .0
+                .iter()
+                .any(|(label, _)| label == "checked"),
+            _ => false,
+        });
+        let text: String = node
+            .content
+            .iter()
+            .filter(|content| {
+                !matches!(content, Content::InnerContent(block)
+                    if matches!(block.tag_name, Tag::Ul | Tag::Ol | Tag::Pre | Tag::P))
+            })
+            .map(|content| plain_text(std::slice::from_ref(content)))
+            .collect();
+        tasks.push(Task {
+            checked,
+            text: text.split_whitespace().collect::<Vec<&str>>().join(" "),
+            line: node.source_span.map_or(0, |span| span.start_line),
+        });
+    }
+    for content in node.content.iter() {
+        if let Content::InnerContent(child) = content {
+            visit_task_items(child, tasks);
+        }
+    }
+}
+
+/// Every `md-task` list item in the node list, read back from the blocks the
+/// parser built so it agrees with the rendered HTML.
+pub fn collect_tasks(node_list: &NodeList) -> Vec<Task> {
+    let mut tasks = vec![];
+    for node in node_list.0.iter() {
+        visit_task_items(node, &mut tasks);
+    }
+    tasks
+}
+
+/// Splits list item content starting with `[ ]`, `[x]` or `[X]` into its
+/// checked state and the remaining text.
+fn parse_task_marker(content: &str) -> Option<(bool, &str)> {
+    let checked = match content.get(..3)? {
+        "[ ]" => false,
+        "[x]" | "[X]" => true,
+        _ => return None,
+    };
+    let text = content[3..].strip_prefix([' ', '\t'])?;
+    Some((checked, text.trim_start()))
 }
 
 impl MarkdownLine {
-    fn assign_node(md_line: MarkdownLine) -> Node {
-        match md_line.line_type {
+    /// A `<li class="md-li md-task">` starting with a disabled checkbox.
+    fn task_item_node(checked: bool, text: &str, references: &ReferenceMap) -> Node {
+        let mut attributes = vec![("type".to_string(), "checkbox".to_string())];
+        if checked {
+            attributes.push(("checked".to_string(), "".to_string()));
+        }
+        attributes.push(("disabled".to_string(), "".to_string()));
+
+        let mut content = vec![
+            Content::InnerContent(Node {
+                class_list: ClassList(vec![]),
+                content: vec![],
+                id: "".to_string(),
+                source_span: None,
+                attributes: Attributes(attributes),
+                tag_name: Tag::Input,
+            }),
+            Content::InnerText(" ".to_string()),
+        ];
+        content.extend(parse_inline(text, references));
+
+        Node {
+            class_list: ClassList(vec!["md-li".to_string(), "md-task".to_string()]),
+            content,
+            id: "".to_string(),
+            source_span: None,
+            attributes: Attributes(vec![]),
+            tag_name: Tag::Li,
+        }
+    }
+
+    fn assign_node(md_line: MarkdownLine, references: &ReferenceMap) -> Node {
+        let span = md_line.span;
+        let mut node = match md_line.line_type {
             MarkDownLineType::H1 => Node {
                 class_list: ClassList(vec!["md-h1-container".to_string()]),
                 content: vec![
                     Content::InnerContent(Node {
                         class_list: ClassList(vec!["md-h1".to_string()]),
                         id: "".to_string(),
-                        content: vec![Content::InnerText(md_line.content)], // tag_name: Tag::H1,
+                        source_span: None,
+                        attributes: Attributes(vec![]),
+                        content: parse_inline(&md_line.content, references), // tag_name: Tag::H1,
                         tag_name: Tag::H1,
                     }),
                     Content::InnerContent(Node {
                         class_list: ClassList(vec!["md-hr".to_string()]),
                         id: "".to_string(),
+                        source_span: None,
+                        attributes: Attributes(vec![]),
                         content: vec![Content::InnerText("".to_string())], // tag_name: Tag::H1,
                         tag_name: Tag::Hr,
                     }),
                 ],
                 id: "".to_string(),
+                source_span: None,
+                attributes: Attributes(vec![]),
                 tag_name: Tag::Div,
             },
             MarkDownLineType::H2 => Node {
                 class_list: ClassList(vec!["md-h2".to_string()]),
-                content: vec![Content::InnerText(md_line.content)],
+                content: parse_inline(&md_line.content, references),
                 id: "".to_string(),
+                source_span: None,
+                attributes: Attributes(vec![]),
                 tag_name: Tag::H2,
             },
             MarkDownLineType::H3 => Node {
                 class_list: ClassList(vec!["md-h3".to_string()]),
-                content: vec![Content::InnerText(md_line.content)],
+                content: parse_inline(&md_line.content, references),
                 id: "".to_string(),
+                source_span: None,
+                attributes: Attributes(vec![]),
                 tag_name: Tag::H3,
             },
             MarkDownLineType::H4 => Node {
                 class_list: ClassList(vec!["md-h4".to_string()]),
-                content: vec![Content::InnerText(md_line.content)],
+                content: parse_inline(&md_line.content, references),
                 id: "".to_string(),
+                source_span: None,
+                attributes: Attributes(vec![]),
                 tag_name: Tag::H4,
             },
-            MarkDownLineType::Li => Node {
-                class_list: ClassList(vec!["md-li".to_string()]),
-                content: vec![Content::InnerText(md_line.content)],
+            MarkDownLineType::H5 => Node {
+                class_list: ClassList(vec!["md-h5".to_string()]),
+                content: parse_inline(&md_line.content, references),
                 id: "".to_string(),
-                tag_name: Tag::Li,
+                source_span: None,
+                attributes: Attributes(vec![]),
+                tag_name: Tag::H5,
             },
-            MarkDownLineType::NoTag => Node {
+            MarkDownLineType::H6 => Node {
+                class_list: ClassList(vec!["md-h6".to_string()]),
+                content: parse_inline(&md_line.content, references),
+                id: "".to_string(),
+                source_span: None,
+                attributes: Attributes(vec![]),
+                tag_name: Tag::H6,
+            },
+            MarkDownLineType::Li | MarkDownLineType::OrderedLi(_) => {
+                match parse_task_marker(&md_line.content) {
+                    Some((checked, text)) => {
+                        MarkdownLine::task_item_node(checked, text, references)
+                    }
+                    None => Node {
+                        class_list: ClassList(vec!["md-li".to_string()]),
+                        content: parse_inline(&md_line.content, references),
+                        id: "".to_string(),
+                        source_span: None,
+                        attributes: Attributes(vec![]),
+                        tag_name: Tag::Li,
+                    },
+                }
+            }
+            MarkDownLineType::IndentedCode => MarkdownLine::code_block_node(
+                None,
+                md_line.content.split('\n').map(String::from).collect(),
+            ),
+            MarkDownLineType::CodeFence
+            | MarkDownLineType::BlockQuote
+            | MarkDownLineType::LinkDefinition
+            | MarkDownLineType::NoTag => Node {
                 class_list: ClassList(vec!["md-p".to_string()]),
-                content: vec![Content::InnerText(md_line.content)],
+                content: parse_inline(&md_line.content, references),
                 id: "".to_string(),
+                source_span: None,
+                attributes: Attributes(vec![]),
                 tag_name: Tag::P,
             },
+            MarkDownLineType::ThematicBreak => Node {
+                class_list: ClassList(vec!["md-hr".to_string()]),
+                content: vec![],
+                id: "".to_string(),
+                source_span: None,
+                attributes: Attributes(vec![]),
+                tag_name: Tag::Hr,
+            },
             MarkDownLineType::EmptyLine => Node {
                 class_list: ClassList(vec!["md-empty-line".to_string()]),
                 content: vec![Content::InnerText("".to_string())],
                 id: "".to_string(),
+                source_span: None,
+                attributes: Attributes(vec![]),
                 tag_name: Tag::Div,
             },
+        };
+        node.source_span = Some(span);
+        node
+    }
+
+    fn code_block_node(language: Option<&str>, body: Vec<String>) -> Node {
+        let class_list = match language {
+            Some(language) => vec![format!("language-{}", language)],
+            None => vec![],
+        };
+        let text = body
+            .iter()
+            .fold("".to_string(), |acc, line| acc + line + "\n");
+
+        Node {
+            class_list: ClassList(vec![]),
+            content: vec![Content::InnerContent(Node {
+                class_list: ClassList(class_list),
+                content: vec![Content::InnerText(text)],
+                id: "".to_string(),
+                source_span: None,
+                attributes: Attributes(vec![]),
+                tag_name: Tag::Code,
+            })],
+            id: "".to_string(),
+            source_span: None,
+            attributes: Attributes(vec![]),
+            tag_name: Tag::Pre,
+        }
+    }
+
+    fn is_list_item(line_type: &MarkDownLineType) -> bool {
+        matches!(
+            line_type,
+            MarkDownLineType::Li | MarkDownLineType::OrderedLi(_)
+        )
+    }
+
+    /// Whether two items at the same level belong in the same list: bullets
+    /// and numbered items each start a list of their own.
+    fn is_same_list(first: &MarkDownLineType, second: &MarkDownLineType) -> bool {
+        matches!(
+            (first, second),
+            (MarkDownLineType::Li, MarkDownLineType::Li)
+                | (
+                    MarkDownLineType::OrderedLi(_),
+                    MarkDownLineType::OrderedLi(_)
+                )
+        )
+    }
+
+    /// The content of a blockquote line after its `>` marker and one optional
+    /// space, or `None` if the line is not part of a blockquote.
+    fn strip_quote_marker(line: &str) -> Option<&str> {
+        let indent = line.len() - line.trim_start_matches(' ').len();
+        if indent > 3 {
+            return None;
+        }
+        let rest = line[indent..].strip_prefix('>')?;
+        Some(rest.strip_prefix(' ').unwrap_or(rest))
+    }
+
+    /// The content of a line inside every level of blockquote it opens.
+    fn get_innermost_quote_content(line: &str) -> &str {
+        let mut content = line;
+        while let Some(inner) = MarkdownLine::strip_quote_marker(content) {
+            content = inner;
+        }
+        content
+    }
+
+    /// Number of lines that make up one blockquote. A line without a `>` still
+    /// belongs to the quote as a lazy continuation when it carries on a
+    /// paragraph inside the quote.
+    fn blockquote_length(md_lines: &[MarkdownLine]) -> usize {
+        let mut in_paragraph = false;
+        let mut length = 0;
+
+        for md_line in md_lines {
+            match md_line.line_type {
+                MarkDownLineType::BlockQuote => {
+                    let content = MarkdownLine::get_innermost_quote_content(&md_line.raw);
+                    in_paragraph = matches!(
+                        MarkdownLine::parse(content.to_string()).line_type,
+                        MarkDownLineType::NoTag
+                    );
+                }
+                MarkDownLineType::NoTag if in_paragraph => {}
+                _ => break,
+            }
+            length += 1;
+        }
+
+        length
+    }
+
+    /// The lines of a blockquote minus their `>` markers. `depth` is the
+    /// number of blockquotes around this one. Past `MAX_QUOTE_DEPTH`, further
+    /// `>` markers are kept as paragraph text instead of opening yet another
+    /// quote.
+    fn quote_content_lines(md_lines: &[MarkdownLine], depth: usize) -> Vec<MarkdownLine> {
+        md_lines
+            .iter()
+            .map(|md_line| {
+                let inner = MarkdownLine::strip_quote_marker(&md_line.raw).unwrap_or(&md_line.raw);
+                let mut inner_line = MarkdownLine::parse(inner.to_string());
+                if depth + 1 >= MAX_QUOTE_DEPTH
+                    && matches!(inner_line.line_type, MarkDownLineType::BlockQuote)
+                {
+                    inner_line.line_type = MarkDownLineType::NoTag;
+                    inner_line.content = inner.trim().to_string();
+                }
+                inner_line.span = suffix_span(&md_line.span, inner);
+                inner_line
+            })
+            .collect()
+    }
+
+    /// Parses the lines of a blockquote, minus their `>` markers, as a
+    /// Markdown document of its own. `depth` is the number of blockquotes
+    /// around this one.
+    fn blockquote_node(md_lines: &[MarkdownLine], references: &ReferenceMap, depth: usize) -> Node {
+        let inner_lines = MarkdownLine::quote_content_lines(md_lines, depth);
+        Node {
+            class_list: ClassList(vec!["md-blockquote".to_string()]),
+            content: MarkdownLine::group_blocks(inner_lines, references, depth + 1)
+                .into_iter()
+                .map(Content::InnerContent)
+                .collect(),
+            id: "".to_string(),
+            source_span: Some(md_lines[0].span.to(&md_lines[md_lines.len() - 1].span)),
+            attributes: Attributes(vec![]),
+            tag_name: Tag::Blockquote,
+        }
+    }
+
+    /// The column at which a list item's content starts, after its marker.
+    fn content_column(item: &MarkdownLine) -> usize {
+        item.raw.len() - item.content.len()
+    }
+
+    /// Number of lines in an indented code block indented by at least
+    /// `columns`. Blank lines only belong to it when more code follows.
+    fn indented_code_length(md_lines: &[MarkdownLine], columns: usize) -> usize {
+        let length = md_lines
+            .iter()
+            .take_while(|md_line| {
+                is_blank(&md_line.raw) || leading_columns(&md_line.raw) >= columns
+            })
+            .count();
+        length
+            - md_lines[..length]
+                .iter()
+                .rev()
+                .take_while(|md_line| is_blank(&md_line.raw))
+                .count()
+    }
+
+    /// Folds the lines of an indented code block into one line whose content
+    /// is the code, with `columns` columns of indentation removed.
+    fn indented_code_line(md_lines: &[MarkdownLine], columns: usize, indent: i32) -> MarkdownLine {
+        let body: Vec<String> = md_lines
+            .iter()
+            .map(|md_line| strip_columns(&md_line.raw, columns))
+            .collect();
+
+        MarkdownLine {
+            content: body.join("\n"),
+            indent,
+            line_type: MarkDownLineType::IndentedCode,
+            raw: md_lines[0].raw.clone(),
+            span: md_lines[0].span.to(&md_lines[md_lines.len() - 1].span),
+        }
+    }
+
+    /// The items of the run of lists starting at the first line, and the
+    /// number of lines the run takes up. Each item's `indent` is set to its
+    /// nesting depth: an item starting at or past the content column of an
+    /// open item is nested inside it. Text lines right after an item are
+    /// wrapped lines of it, however far they are indented, because an indented
+    /// code block can't interrupt a paragraph. Blank lines only belong to the
+    /// run when another item follows. After a blank line, text indented to an
+    /// item's content column is a paragraph inside that item, and lines
+    /// indented four columns past it are an indented code block inside it.
+    /// Both are returned as a single `NoTag` or `IndentedCode` line one level
+    /// deeper than the item.
+    fn list_items(md_lines: &[MarkdownLine]) -> (Vec<MarkdownLine>, usize) {
+        let mut items: Vec<MarkdownLine> = vec![];
+        // Content columns of the items a following line can belong to,
+        // outermost first.
+        let mut open_columns: Vec<usize> = vec![];
+        let mut length = 0;
+        let mut index = 0;
+
+        while index < md_lines.len() {
+            let md_line = &md_lines[index];
+            match md_line.line_type {
+                MarkDownLineType::EmptyLine => index += 1,
+                ref line_type if MarkdownLine::is_list_item(line_type) => {
+                    let column = leading_columns(&md_line.raw);
+                    while open_columns.last().is_some_and(|&open| column < open) {
+                        open_columns.pop();
+                    }
+                    let mut item = md_line.clone();
+                    item.indent = open_columns.len() as i32;
+                    open_columns.push(MarkdownLine::content_column(md_line));
+                    items.push(item);
+                    index += 1;
+                    length = index;
+                }
+                _ => {
+                    let after_blank =
+                        matches!(md_lines[index - 1].line_type, MarkDownLineType::EmptyLine);
+                    if !after_blank {
+                        let owner = items.last_mut().unwrap();
+                        let is_text = matches!(md_line.line_type, MarkDownLineType::NoTag)
+                            && MarkdownLine::is_list_item(&owner.line_type)
+                            && MarkdownLine::get_table_alignments(&md_lines[index..]).is_none();
+                        if !is_text {
+                            break;
+                        }
+                        owner.content = format!("{}\n{}", owner.content, md_line.raw.trim_start());
+                        owner.span = owner.span.to(&md_line.span);
+                        index += 1;
+                        length = index;
+                        continue;
+                    }
+                    let column = leading_columns(&md_line.raw);
+                    while open_columns.last().is_some_and(|&open| column < open) {
+                        open_columns.pop();
+                    }
+                    let Some(&content_column) = open_columns.last() else {
+                        break;
+                    };
+                    let columns = content_column + 4;
+                    if column < columns {
+                        // Only text that is still text once the item's
+                        // indentation is removed continues the item.
+                        let inner = strip_columns(&md_line.raw, content_column);
+                        if !matches!(
+                            MarkdownLine::parse(inner).line_type,
+                            MarkDownLineType::NoTag
+                        ) {
+                            break;
+                        }
+                        let paragraph_length = 1 + md_lines[index + 1..]
+                            .iter()
+                            .take_while(|md_line| {
+                                matches!(md_line.line_type, MarkDownLineType::NoTag)
+                            })
+                            .count();
+                        let paragraph = &md_lines[index..index + paragraph_length];
+                        let mut line = md_line.clone();
+                        line.content = paragraph
+                            .iter()
+                            .map(|md_line| md_line.raw.trim())
+                            .collect::<Vec<&str>>()
+                            .join("\n");
+                        line.indent = open_columns.len() as i32;
+                        line.span = md_line.span.to(&paragraph[paragraph_length - 1].span);
+                        items.push(line);
+                        index += paragraph_length;
+                        length = index;
+                        continue;
+                    }
+                    let code_length =
+                        MarkdownLine::indented_code_length(&md_lines[index..], columns);
+                    let code = &md_lines[index..index + code_length];
+                    items.push(MarkdownLine::indented_code_line(
+                        code,
+                        columns,
+                        open_columns.len() as i32,
+                    ));
+                    index += code_length;
+                    length = index;
+                }
+            }
+        }
+
+        (items, length)
+    }
+
+    /// Splits a run of list items into `<ul>` and `<ol>` lists, starting a new
+    /// list whenever the item kind changes at the level of the first item.
+    fn list_nodes(items: &[MarkdownLine], references: &ReferenceMap) -> Vec<Node> {
+        let mut lists = vec![];
+        let mut index = 0;
+
+        while index < items.len() {
+            let first = &items[index];
+            let length = items[index + 1..]
+                .iter()
+                .take_while(|item| {
+                    item.indent > first.indent
+                        || MarkdownLine::is_same_list(&first.line_type, &item.line_type)
+                })
+                .count()
+                + 1;
+            lists.push(MarkdownLine::list_node(
+                &items[index..index + length],
+                references,
+            ));
+            index += length;
+        }
+
+        lists
+    }
+
+    /// Wraps a run of same-kind list items in a `<ul>` or `<ol>`. Items
+    /// indented deeper than the first one are nested in child lists inside the
+    /// preceding `<li>`.
+    fn list_node(items: &[MarkdownLine], references: &ReferenceMap) -> Node {
+        let base_indent = items[0].indent;
+        let mut list_items = vec![];
+        let mut index = 0;
+
+        while index < items.len() {
+            let nested_length = items[index + 1..]
+                .iter()
+                .take_while(|item| item.indent > base_indent)
+                .count();
+            let mut li = MarkdownLine::assign_node(items[index].clone(), references);
+            let nested = &items[index + 1..index + 1 + nested_length];
+            // Paragraphs and code blocks of this item sit one level deeper
+            // than it, between the items of its child lists.
+            let mut rest = nested;
+            while let Some(first) = rest.first() {
+                if !MarkdownLine::is_list_item(&first.line_type) {
+                    let block = MarkdownLine::assign_node(first.clone(), references);
+                    li.content.push(Content::InnerContent(block));
+                    rest = &rest[1..];
+                    continue;
+                }
+                let lists_length = 1 + rest[1..]
+                    .iter()
+                    .take_while(|item| {
+                        item.indent > first.indent || MarkdownLine::is_list_item(&item.line_type)
+                    })
+                    .count();
+                for list in MarkdownLine::list_nodes(&rest[..lists_length], references) {
+                    li.content.push(Content::InnerContent(list));
+                }
+                rest = &rest[lists_length..];
+            }
+            if let Some(last) = nested.last() {
+                li.source_span = Some(items[index].span.to(&last.span));
+            }
+            list_items.push(Content::InnerContent(li));
+            index += nested_length + 1;
+        }
+
+        let list_span = items[0].span.to(&items[items.len() - 1].span);
+        match items[0].line_type {
+            MarkDownLineType::OrderedLi(start) => Node {
+                class_list: ClassList(vec!["md-ol".to_string()]),
+                content: list_items,
+                id: "".to_string(),
+                source_span: Some(list_span),
+                attributes: Attributes(if start == 1 {
+                    vec![]
+                } else {
+                    vec![("start".to_string(), start.to_string())]
+                }),
+                tag_name: Tag::Ol,
+            },
+            _ => Node {
+                class_list: ClassList(vec!["md-ul".to_string()]),
+                content: list_items,
+                id: "".to_string(),
+                source_span: Some(list_span),
+                attributes: Attributes(vec![]),
+                tag_name: Tag::Ul,
+            },
+        }
+    }
+
+    /// The column alignments if `md_lines` starts with a table: a header row
+    /// containing a pipe, followed by a delimiter row with as many cells.
+    fn get_table_alignments(md_lines: &[MarkdownLine]) -> Option<Vec<Alignment>> {
+        let header = md_lines.first()?;
+        let delimiter = md_lines.get(1)?;
+        if !matches!(header.line_type, MarkDownLineType::NoTag) || !header.raw.contains('|') {
+            return None;
+        }
+        let alignments = parse_delimiter_row(&delimiter.raw)?;
+        if alignments.len() != split_table_row(&header.raw).len() {
+            return None;
+        }
+        Some(alignments)
+    }
+
+    /// Number of lines in the table starting `md_lines`: the header row, the
+    /// delimiter row and every body row up to a blank line or another block.
+    fn table_length(md_lines: &[MarkdownLine]) -> usize {
+        2 + md_lines[2..]
+            .iter()
+            .take_while(|md_line| matches!(md_line.line_type, MarkDownLineType::NoTag))
+            .count()
+    }
+
+    /// A `<tr>` with one cell per column. Missing cells are left empty and
+    /// cells beyond the header's column count are dropped.
+    fn table_row_node(
+        md_line: &MarkdownLine,
+        cell_tag: Tag,
+        alignments: &[Alignment],
+        references: &ReferenceMap,
+    ) -> Node {
+        let mut cells = split_table_row(&md_line.raw).into_iter();
+        let content = alignments
+            .iter()
+            .map(|alignment| {
+                let text = cells.next().unwrap_or_default();
+                Content::InnerContent(Node {
+                    class_list: alignment.class_list(),
+                    content: parse_inline(&text, references),
+                    id: "".to_string(),
+                    source_span: None,
+                    attributes: Attributes(vec![]),
+                    tag_name: cell_tag,
+                })
+            })
+            .collect();
+
+        Node {
+            class_list: ClassList(vec![]),
+            content,
+            id: "".to_string(),
+            source_span: Some(md_line.span),
+            attributes: Attributes(vec![]),
+            tag_name: Tag::Tr,
         }
     }
 
-    pub fn create_node_list(lines: String) -> NodeList {
-        let md_lines = MarkdownLine::get_md_lines(lines);
-        let node_vec = md_lines
+    /// A GFM pipe table. The `<tbody>` is left out when there are no body
+    /// rows.
+    fn table_node(
+        md_lines: &[MarkdownLine],
+        alignments: &[Alignment],
+        references: &ReferenceMap,
+    ) -> Node {
+        let section = |tag_name: Tag, rows: Vec<Content>| {
+            Content::InnerContent(Node {
+                class_list: ClassList(vec![]),
+                content: rows,
+                id: "".to_string(),
+                source_span: None,
+                attributes: Attributes(vec![]),
+                tag_name,
+            })
+        };
+
+        let header = MarkdownLine::table_row_node(&md_lines[0], Tag::Th, alignments, references);
+        let mut content = vec![section(Tag::Thead, vec![Content::InnerContent(header)])];
+        let body: Vec<Content> = md_lines[2..]
             .iter()
-            .map(|md_line| MarkdownLine::assign_node(md_line.clone()))
+            .map(|md_line| {
+                Content::InnerContent(MarkdownLine::table_row_node(
+                    md_line,
+                    Tag::Td,
+                    alignments,
+                    references,
+                ))
+            })
             .collect();
+        if !body.is_empty() {
+            content.push(section(Tag::Tbody, body));
+        }
+
+        Node {
+            class_list: ClassList(vec!["md-table".to_string()]),
+            content,
+            id: "".to_string(),
+            source_span: Some(md_lines[0].span.to(&md_lines[md_lines.len() - 1].span)),
+            attributes: Attributes(vec![]),
+            tag_name: Tag::Table,
+        }
+    }
+
+    /// Block stage: folds runs of lines that belong together (such as a fenced
+    /// code block, a list, a blockquote or a paragraph) into a single node, and
+    /// assigns every other line on its own.
+    fn group_blocks(
+        md_lines: Vec<MarkdownLine>,
+        references: &ReferenceMap,
+        quote_depth: usize,
+    ) -> Vec<Node> {
+        let mut nodes = vec![];
+        let mut index = 0;
+
+        while index < md_lines.len() {
+            let md_line = &md_lines[index];
+
+            // Checked before the line type: four columns of indentation turn
+            // even a list item or a header into code.
+            if !is_blank(&md_line.raw) && leading_columns(&md_line.raw) >= 4 {
+                let length = MarkdownLine::indented_code_length(&md_lines[index..], 4);
+                let code = MarkdownLine::indented_code_line(&md_lines[index..index + length], 4, 0);
+                index += length;
+                nodes.push(MarkdownLine::assign_node(code, references));
+                continue;
+            }
+
+            match md_line.line_type {
+                MarkDownLineType::CodeFence => {
+                    let fence = Fence::parse(&md_line.raw).unwrap();
+                    let body: Vec<String> = md_lines[index + 1..]
+                        .iter()
+                        .take_while(|line| !fence.is_closed_by(&line.raw))
+                        .map(|line| fence.strip_indent(&line.raw).to_string())
+                        .collect();
+                    let last = (index + body.len() + 1).min(md_lines.len() - 1);
+                    let mut code_block = MarkdownLine::code_block_node(fence.language(), body);
+                    code_block.source_span = Some(md_line.span.to(&md_lines[last].span));
+                    index = last + 1;
+                    nodes.push(code_block);
+                }
+                MarkDownLineType::Li | MarkDownLineType::OrderedLi(_) => {
+                    let (items, length) = MarkdownLine::list_items(&md_lines[index..]);
+                    index += length;
+                    nodes.extend(MarkdownLine::list_nodes(&items, references));
+                }
+                MarkDownLineType::BlockQuote => {
+                    let length = MarkdownLine::blockquote_length(&md_lines[index..]);
+                    let lines = &md_lines[index..index + length];
+                    index += length;
+                    nodes.push(MarkdownLine::blockquote_node(
+                        lines,
+                        references,
+                        quote_depth,
+                    ));
+                }
+                MarkDownLineType::LinkDefinition => index += 1,
+                MarkDownLineType::NoTag
+                    if MarkdownLine::get_table_alignments(&md_lines[index..]).is_some() =>
+                {
+                    let alignments =
+                        MarkdownLine::get_table_alignments(&md_lines[index..]).unwrap();
+                    let length = MarkdownLine::table_length(&md_lines[index..]);
+                    let lines = &md_lines[index..index + length];
+                    index += length;
+                    nodes.push(MarkdownLine::table_node(lines, &alignments, references));
+                }
+                MarkDownLineType::NoTag => {
+                    let length = MarkdownLine::paragraph_length(&md_lines[index..]);
+                    let lines = &md_lines[index..index + length];
+                    index += length;
+
+                    // Paragraph lines directly followed by an underline are a
+                    // Setext header instead.
+                    let setext_tag = md_lines
+                        .get(index)
+                        .and_then(|underline| MarkdownLine::get_setext_tag(&underline.raw));
+                    let paragraph = match setext_tag {
+                        Some(line_type) => {
+                            let mut header = MarkdownLine::join_paragraph(lines, line_type);
+                            header.span = header.span.to(&md_lines[index].span);
+                            index += 1;
+                            header
+                        }
+                        None => MarkdownLine::join_paragraph(lines, MarkDownLineType::NoTag),
+                    };
+                    nodes.push(MarkdownLine::assign_node(paragraph, references));
+                }
+                _ => {
+                    nodes.push(MarkdownLine::assign_node(md_line.clone(), references));
+                    index += 1;
+                }
+            }
+        }
+
+        nodes
+    }
+
+    /// Pre-pass collecting every `[label]: url "title"` definition in the
+    /// document, so reference links can point forward as well as back.
+    fn collect_references(md_lines: &[MarkdownLine]) -> ReferenceMap {
+        let mut references = ReferenceMap::default();
+        MarkdownLine::collect_references_into(md_lines, &mut references, 0);
+        references
+    }
+
+    /// Adds the definitions in `md_lines` to `references`. Blockquotes are
+    /// read as documents of their own, as `blockquote_node` parses them, so
+    /// a fence inside a quote hides its lines as well. `depth` is the number
+    /// of blockquotes around `md_lines`.
+    fn collect_references_into(
+        md_lines: &[MarkdownLine],
+        references: &mut ReferenceMap,
+        depth: usize,
+    ) {
+        let mut open_fence: Option<Fence> = None;
+        let mut index = 0;
+
+        while index < md_lines.len() {
+            let md_line = &md_lines[index];
+            index += 1;
+            match (&open_fence, &md_line.line_type) {
+                (Some(fence), _) if fence.is_closed_by(&md_line.raw) => open_fence = None,
+                (Some(_), _) => {}
+                (None, MarkDownLineType::CodeFence) => open_fence = Fence::parse(&md_line.raw),
+                (None, MarkDownLineType::LinkDefinition) => {
+                    if let Some((label, reference)) = parse_link_definition(&md_line.raw) {
+                        references.insert(&label, reference);
+                    }
+                }
+                (None, MarkDownLineType::BlockQuote) if depth < MAX_QUOTE_DEPTH => {
+                    let quote = &md_lines[index - 1..];
+                    let length = MarkdownLine::blockquote_length(quote);
+                    let inner_lines = MarkdownLine::quote_content_lines(&quote[..length], depth);
+                    MarkdownLine::collect_references_into(&inner_lines, references, depth + 1);
+                    index += length - 1;
+                }
+                _ => {}
+            }
+        }
+    }
 
-        NodeList(node_vec)
+    fn build_node_list(md_lines: Vec<MarkdownLine>) -> (NodeList, Outline) {
+        let references = MarkdownLine::collect_references(&md_lines);
+        let mut node_list = NodeList(MarkdownLine::group_blocks(md_lines, &references, 0));
+        let outline = assign_heading_ids(&mut node_list);
+        (node_list, outline)
     }
 
-    pub fn parse_markdown_to_html(lines: String) -> String {
-        format!("{}", MarkdownLine::create_node_list(lines))
+    /// Parses a whole document at once into its nodes, its outline and the
+    /// diagnostics found along the way.
+    pub fn parse_document(lines: &str) -> (NodeList, Outline, Vec<Diagnostic>) {
+        let md_lines = MarkdownLine::get_md_lines(lines.to_string());
+        let diagnostics = MarkdownLine::collect_diagnostics(&md_lines);
+        let (node_list, outline) = MarkdownLine::build_node_list(md_lines);
+        (node_list, outline, diagnostics)
     }
 
+    /// A warning for a line that looks like a header but is rendered as
+    /// text. `text` is the line, or its content inside blockquote markers
+    /// that take up the first `offset` characters of the line.
+    fn header_diagnostic(text: &str, line: usize, offset: usize) -> Option<Diagnostic> {
+        let trimmed = text.trim_start();
+        let hashes = trimmed.bytes().take_while(|&byte| byte == b'#').count();
+        if hashes == 0 {
+            return None;
+        }
+        let kind = match trimmed[hashes..].chars().next() {
+            None => return None,
+            Some(' ' | '\t') if hashes > 6 => DiagnosticKind::HeaderTooDeep,
+            Some(' ' | '\t') if text != trimmed => DiagnosticKind::IndentedHeader,
+            Some(' ' | '\t') => return None,
+            Some(_) => DiagnosticKind::MissingHeaderSpace,
+        };
+
+        let indent = text.chars().count() - trimmed.chars().count();
+        Some(Diagnostic::new(kind, line, offset + indent + 1))
+    }
+
+    /// Pre-pass looking for lines that were probably not meant to render the
+    /// way they do, such as `#Title` or a code fence that is never closed.
+    fn collect_diagnostics(md_lines: &[MarkdownLine]) -> Vec<Diagnostic> {
+        let mut diagnostics = vec![];
+        let mut open_fence: Option<(Fence, usize)> = None;
+
+        for (index, md_line) in md_lines.iter().enumerate() {
+            let line = index + 1;
+            match (&open_fence, &md_line.line_type) {
+                (Some((fence, _)), _) if fence.is_closed_by(&md_line.raw) => open_fence = None,
+                (Some(_), _) => {}
+                (None, MarkDownLineType::CodeFence) => {
+                    open_fence = Fence::parse(&md_line.raw).map(|fence| (fence, line));
+                }
+                (None, MarkDownLineType::NoTag) if leading_columns(&md_line.raw) < 4 => {
+                    diagnostics.extend(MarkdownLine::header_diagnostic(&md_line.raw, line, 0));
+                }
+                (None, MarkDownLineType::BlockQuote) => {
+                    let content = MarkdownLine::get_innermost_quote_content(&md_line.raw);
+                    let offset = md_line.raw.chars().count() - content.chars().count();
+                    if matches!(
+                        MarkdownLine::parse(content.to_string()).line_type,
+                        MarkDownLineType::NoTag
+                    ) {
+                        diagnostics.extend(MarkdownLine::header_diagnostic(content, line, offset));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if let Some((fence, line)) = open_fence {
+            diagnostics.push(Diagnostic::new(
+                DiagnosticKind::UnclosedCodeFence,
+                line,
+                fence.indent + 1,
+            ));
+        }
+
+        diagnostics
+    }
+
+    /// Indent level of a line: the column of its first character that is
+    /// neither whitespace nor a hyphen, in steps of four, with tabs advancing
+    /// to the next multiple of four. `-1` for a line with no such character.
     fn get_indent(line: String) -> i32 {
-        let first_char_position = line
-            .split("")
-            .position(|x| Regex::new(r"[^\s-]").unwrap().is_match(x));
+        if OrderedMarker::parse(&line).is_some() {
+            return leading_columns(&line).div_ceil(4) as i32;
+        }
 
-        match first_char_position {
-            Some(n) => (n / 4) as i32,
-            None => -1,
+        let mut column: usize = 0;
+        for c in line.chars() {
+            match c {
+                '\t' => column += 4 - column % 4,
+                _ if c.is_whitespace() || c == '-' => column += 1,
+                _ => return ((column + 1) / 4) as i32,
+            }
         }
+        -1
     }
 
     fn get_md_lines(lines: String) -> Vec<MarkdownLine> {
+        let mut offset = 0;
         lines
             .split("\n")
-            .map(|line| MarkdownLine::parse(line.to_string()))
+            .enumerate()
+            .map(|(index, line)| {
+                let mut md_line = MarkdownLine::parse(line.to_string());
+                md_line.span = line_span(line, index + 1, offset);
+                offset += line.len() + 1;
+                md_line
+            })
             .collect()
     }
 
@@ -132,6 +1231,8 @@ impl MarkdownLine {
             3 => MarkDownLineType::H2,
             4 => MarkDownLineType::H3,
             5 => MarkDownLineType::H4,
+            6 => MarkDownLineType::H5,
+            7 => MarkDownLineType::H6,
             _ => MarkDownLineType::NoTag,
         }
     }
@@ -141,66 +1242,148 @@ impl MarkdownLine {
             return MarkDownLineType::EmptyLine;
         };
 
-        if indent > 0 {
-            return MarkDownLineType::NoTag;
-        };
+        if let Some(marker) = OrderedMarker::parse(&line) {
+            return MarkDownLineType::OrderedLi(marker.start);
+        }
 
+        // The line starts with a run of `#` or `-` markers, which must be
+        // followed by a space or a tab.
         let trimmed = line.trim();
-        let split = trimmed.split("");
-        let first_tag_regex = Regex::new(r"[#|-]").unwrap();
-        let first_tag_position = split.clone().position(|x| first_tag_regex.is_match(x));
-        let first_tag = split.clone().find(|x| first_tag_regex.is_match(x));
-
-        let first_non_tag_regex = Regex::new(r"[^#|^-]").unwrap();
-        let first_non_tag_char_pos = split.clone().position(|x| first_non_tag_regex.is_match(x));
-
-        if first_non_tag_char_pos == None {
+        let marker_length = trimmed
+            .bytes()
+            .take_while(|&byte| byte == b'#' || byte == b'-')
+            .count();
+        if marker_length == 0
+            || !matches!(trimmed.as_bytes().get(marker_length), Some(b' ' | b'\t'))
+        {
             return MarkDownLineType::NoTag;
-        };
-
-        let first_non_tag_char = trimmed.chars().nth(first_non_tag_char_pos.unwrap() - 1);
+        }
 
-        match first_non_tag_char {
-            None => return MarkDownLineType::EmptyLine,
-            Some(char) => {
-                if char.to_string() != " ".to_string() {
+        match &trimmed[..marker_length] {
+            "-" => MarkDownLineType::Li,
+            marker if marker.bytes().all(|byte| byte == b'#') => {
+                if indent > 0 {
                     return MarkDownLineType::NoTag;
                 }
+                MarkdownLine::get_header_tag(marker_length as i32 + 1)
             }
+            _ => MarkDownLineType::NoTag,
+        }
+    }
+
+    fn get_ordered_content(line: &str) -> String {
+        match OrderedMarker::parse(line) {
+            Some(marker) => marker.content(line).to_string(),
+            None => line.to_string(),
+        }
+    }
+
+    /// Text of an ATX header after its opening `#`s, without the optional
+    /// closing sequence of `#`s.
+    fn get_header_content(line: &str, level: usize) -> String {
+        let content = line.trim_start()[level..].trim();
+        let without_closing = content.trim_end_matches('#');
+        if without_closing.is_empty() || without_closing.ends_with(' ') {
+            without_closing.trim_end().to_string()
+        } else {
+            content.to_string()
+        }
+    }
+
+    /// Whether a line is a thematic break: three or more matching `-`, `*` or
+    /// `_` characters, optionally separated by spaces, indented by no more
+    /// than three spaces.
+    fn is_thematic_break(line: &str) -> bool {
+        let indent = line.len() - line.trim_start_matches(' ').len();
+        let mut markers = line.chars().filter(|c| !c.is_whitespace());
+        let marker = match markers.next() {
+            Some(marker @ ('-' | '*' | '_')) => marker,
+            _ => return false,
         };
+        indent <= 3 && markers.clone().all(|c| c == marker) && markers.count() >= 2
+    }
 
-        if first_tag.unwrap() == "#" {
-            return MarkdownLine::get_header_tag(first_non_tag_char_pos.unwrap() as i32);
+    /// The header type of a Setext underline: a line of `=` for a level one
+    /// header or `-` for level two, indented by no more than three spaces.
+    fn get_setext_tag(line: &str) -> Option<MarkDownLineType> {
+        let indent = line.len() - line.trim_start_matches(' ').len();
+        let trimmed = line.trim();
+        if indent > 3 || trimmed.is_empty() {
+            return None;
+        }
+        if trimmed.chars().all(|c| c == '=') {
+            Some(MarkDownLineType::H1)
+        } else if trimmed.chars().all(|c| c == '-') {
+            Some(MarkDownLineType::H2)
         } else {
-            let second_hyphen = trimmed.chars().nth(first_tag_position.unwrap() as usize);
-            if second_hyphen == Some('-') {
-                return MarkDownLineType::NoTag;
-            }
-            return MarkDownLineType::Li;
+            None
+        }
+    }
+
+    /// Number of consecutive paragraph lines at the start of `md_lines`. A
+    /// Setext underline or the start of a table ends the paragraph, unless it
+    /// is the first line. Lines indented by four or more columns continue the
+    /// paragraph, since an indented code block cannot interrupt it.
+    fn paragraph_length(md_lines: &[MarkdownLine]) -> usize {
+        1 + (1..md_lines.len())
+            .take_while(|&index| {
+                let md_line = &md_lines[index];
+                let is_continuation = matches!(md_line.line_type, MarkDownLineType::NoTag)
+                    || (!is_blank(&md_line.raw) && leading_columns(&md_line.raw) >= 4);
+                is_continuation
+                    && MarkdownLine::get_setext_tag(&md_line.raw).is_none()
+                    && MarkdownLine::get_table_alignments(&md_lines[index..]).is_none()
+            })
+            .count()
+    }
+
+    /// Joins paragraph lines into one line of content separated by newlines.
+    /// Leading whitespace is dropped, but trailing spaces and backslashes are
+    /// kept for the inline parser to turn into hard breaks.
+    fn join_paragraph(md_lines: &[MarkdownLine], line_type: MarkDownLineType) -> MarkdownLine {
+        let lines: Vec<&str> = md_lines
+            .iter()
+            .map(|md_line| md_line.raw.trim_start())
+            .collect();
+
+        MarkdownLine {
+            content: lines.join("\n").trim_end().to_string(),
+            indent: 0,
+            line_type,
+            raw: md_lines[0].raw.clone(),
+            span: md_lines[0].span.to(&md_lines[md_lines.len() - 1].span),
+        }
+    }
+
+    /// Text of a bullet list item after its `-` marker and the space or tab
+    /// that follows it.
+    fn get_li_content(line: &str) -> String {
+        let after_marker = line.trim_start().strip_prefix('-').unwrap_or(line);
+        let mut chars = after_marker.chars();
+        match chars.next() {
+            Some(' ' | '\t') => chars.as_str().to_string(),
+            _ => after_marker.to_string(),
         }
     }
 
     fn get_content(line: String, line_type: &MarkDownLineType, indent: i32) -> String {
-        let len = line.len();
         if indent == 0 {
             return match &line_type {
-                MarkDownLineType::H1 => line[2..len].to_string(),
-                MarkDownLineType::H2 => line[3..len].to_string(),
-                MarkDownLineType::H3 => line[4..len].to_string(),
-                MarkDownLineType::H4 => line[5..len].to_string(),
-                MarkDownLineType::Li => line[2..len].to_string(),
+                MarkDownLineType::H1 => MarkdownLine::get_header_content(&line, 1),
+                MarkDownLineType::H2 => MarkdownLine::get_header_content(&line, 2),
+                MarkDownLineType::H3 => MarkdownLine::get_header_content(&line, 3),
+                MarkDownLineType::H4 => MarkdownLine::get_header_content(&line, 4),
+                MarkDownLineType::H5 => MarkdownLine::get_header_content(&line, 5),
+                MarkDownLineType::H6 => MarkdownLine::get_header_content(&line, 6),
+                MarkDownLineType::Li => MarkdownLine::get_li_content(&line),
+                MarkDownLineType::OrderedLi(_) => MarkdownLine::get_ordered_content(&line),
                 _ => line,
             };
         }
         if indent != 0 {
-            let starting_index = 2 + 4 * indent as usize;
-            println!("indent{}", indent);
-            println!("starting index {}", starting_index);
             return match &line_type {
-                MarkDownLineType::Li => {
-                    println!("is li");
-                    line[starting_index..len].to_string()
-                }
+                MarkDownLineType::Li => MarkdownLine::get_li_content(&line),
+                MarkDownLineType::OrderedLi(_) => MarkdownLine::get_ordered_content(&line),
                 _ => line,
             };
         }
@@ -208,34 +1391,85 @@ impl MarkdownLine {
     }
 
     pub fn parse(line: String) -> MarkdownLine {
+        if let Some(fence) = Fence::parse(&line) {
+            return MarkdownLine {
+                content: fence.info,
+                indent: 0,
+                line_type: MarkDownLineType::CodeFence,
+                raw: line,
+                span: SourceSpan::default(),
+            };
+        }
+
+        if parse_link_definition(&line).is_some() {
+            return MarkdownLine {
+                content: line.trim().to_string(),
+                indent: 0,
+                line_type: MarkDownLineType::LinkDefinition,
+                raw: line,
+                span: SourceSpan::default(),
+            };
+        }
+
+        if let Some(content) = MarkdownLine::strip_quote_marker(&line) {
+            return MarkdownLine {
+                content: content.to_string(),
+                indent: 0,
+                line_type: MarkDownLineType::BlockQuote,
+                raw: line,
+                span: SourceSpan::default(),
+            };
+        }
+
+        // Checked before list items, so that `- - -` is a break and not a bullet.
+        if MarkdownLine::is_thematic_break(&line) {
+            return MarkdownLine {
+                content: "".to_string(),
+                indent: 0,
+                line_type: MarkDownLineType::ThematicBreak,
+                raw: line,
+                span: SourceSpan::default(),
+            };
+        }
+
         let indent = MarkdownLine::get_indent(line.clone());
         if indent == -1 {
             return MarkdownLine {
                 content: "".to_string(),
                 indent: -1,
                 line_type: MarkDownLineType::EmptyLine,
+                raw: line,
+                span: SourceSpan::default(),
             };
         };
         let tag = MarkdownLine::get_tag(line.clone(), indent);
-        let content = MarkdownLine::get_content(line, &tag, indent);
+        let content = MarkdownLine::get_content(line.clone(), &tag, indent);
 
-        return MarkdownLine {
-            content: content,
-            indent: indent,
+        MarkdownLine {
+            content,
+            indent,
             line_type: tag,
-        };
+            raw: line,
+            span: SourceSpan::default(),
+        }
     }
 }
 
+#[cfg(test)]
 mod tests {
     use std::fs;
 
+    use proptest::prelude::*;
+
+    use crate::diagnostics::DiagnosticKind;
     use crate::markdown_parser::split_by_newline;
+    use crate::markdown_parser::{
+        leading_columns, parse_delimiter_row, split_table_row, strip_columns, Alignment,
+        MAX_QUOTE_DEPTH,
+    };
     use crate::markdown_parser::{MarkDownLineType, MarkdownLine};
-
-    fn md_test_file_1() -> String {
-        fs::read_to_string("src/data/md-test-file-1.md").unwrap()
-    }
+    use crate::node::{HtmlFlavor, RenderOptions, SourceSpan};
+    use crate::{render_html, render_html_with_options};
 
     #[test]
     fn t_split_by_newline() {
@@ -264,105 +1498,86 @@ mod tests {
         let line = "hello".to_string();
         let indent = 0;
         let tag = MarkdownLine::get_tag(line, indent);
-        assert!(match tag {
-            MarkDownLineType::NoTag => true,
-            _ => false,
-        });
+        assert!(matches!(tag, MarkDownLineType::NoTag));
 
         let line = " hello".to_string();
         let indent = 0;
         let tag = MarkdownLine::get_tag(line, indent);
-        assert!(match tag {
-            MarkDownLineType::NoTag => true,
-            _ => false,
-        });
+        assert!(matches!(tag, MarkDownLineType::NoTag));
 
         let line = "#hello".to_string();
         let indent = 0;
         let tag = MarkdownLine::get_tag(line, indent);
-        assert!(match tag {
-            MarkDownLineType::NoTag => true,
-            _ => false,
-        });
+        assert!(matches!(tag, MarkDownLineType::NoTag));
 
         let line = "# hello".to_string();
         let indent = 0;
         let tag = MarkdownLine::get_tag(line, indent);
-        assert!(match tag {
-            MarkDownLineType::H1 => true,
-            _ => false,
-        });
+        assert!(matches!(tag, MarkDownLineType::H1));
 
         let line = "## hello".to_string();
         let indent = 0;
         let tag = MarkdownLine::get_tag(line, indent);
-        assert!(match tag {
-            MarkDownLineType::H2 => true,
-            _ => false,
-        });
+        assert!(matches!(tag, MarkDownLineType::H2));
 
         let line = "#### hello".to_string();
         let indent = 0;
         let tag = MarkdownLine::get_tag(line, indent);
 
-        assert!(match tag {
-            MarkDownLineType::H4 => true,
-            _ => false,
-        });
+        assert!(matches!(tag, MarkDownLineType::H4));
 
         let line = "##### hello".to_string();
         let indent = 0;
         let tag = MarkdownLine::get_tag(line, indent);
 
-        assert!(match tag {
-            MarkDownLineType::NoTag => true,
-            _ => false,
-        });
+        assert!(matches!(tag, MarkDownLineType::H5));
+
+        let line = "###### hello".to_string();
+        let indent = 0;
+        let tag = MarkdownLine::get_tag(line, indent);
+
+        assert!(matches!(tag, MarkDownLineType::H6));
+
+        let line = "####### hello".to_string();
+        let indent = 0;
+        let tag = MarkdownLine::get_tag(line, indent);
+
+        assert!(matches!(tag, MarkDownLineType::NoTag));
 
         let line = "- ".to_string();
         let indent = 0;
         let tag = MarkdownLine::get_tag(line, indent);
 
-        assert!(match tag {
-            MarkDownLineType::NoTag => true,
-            _ => false,
-        });
+        assert!(matches!(tag, MarkDownLineType::NoTag));
 
         let line = "- bullet".to_string();
         let indent = 0;
         let tag = MarkdownLine::get_tag(line, indent);
 
-        assert!(match tag {
-            MarkDownLineType::Li => true,
-            _ => false,
-        });
+        assert!(matches!(tag, MarkDownLineType::Li));
 
         let line = "-- bullet".to_string();
         let indent = 0;
         let tag = MarkdownLine::get_tag(line, indent);
 
-        assert!(match tag {
-            MarkDownLineType::NoTag => true,
-            _ => false,
-        });
+        assert!(matches!(tag, MarkDownLineType::NoTag));
 
         let line = "--- bullet".to_string();
         let indent = 0;
         let tag = MarkdownLine::get_tag(line, indent);
 
-        assert!(match tag {
-            MarkDownLineType::NoTag => true,
-            _ => false,
-        });
+        assert!(matches!(tag, MarkDownLineType::NoTag));
 
         let line = "- my bullet".to_string();
         let indent = 0;
         let tag = MarkdownLine::get_tag(line, indent);
 
-        assert!(match tag {
-            MarkDownLineType::Li => true,
-            _ => false,
-        });
+        assert!(matches!(tag, MarkDownLineType::Li));
+        let line = "| a | b |".to_string();
+        let indent = 0;
+        let tag = MarkdownLine::get_tag(line, indent);
+
+        assert!(matches!(tag, MarkDownLineType::NoTag));
     }
 
     #[test]
@@ -378,6 +1593,71 @@ mod tests {
         let indent = 0;
         let content = MarkdownLine::get_content(line, &line_type, indent);
         assert_eq!(content, "header");
+
+        let line = "###### header ###  ".to_string();
+        let line_type = MarkDownLineType::H6;
+        let indent = 0;
+        let content = MarkdownLine::get_content(line, &line_type, indent);
+        assert_eq!(content, "header");
+
+        let line = "# C#".to_string();
+        let line_type = MarkDownLineType::H1;
+        let indent = 0;
+        let content = MarkdownLine::get_content(line, &line_type, indent);
+        assert_eq!(content, "C#");
+
+        let line = "## ##".to_string();
+        let line_type = MarkDownLineType::H2;
+        let indent = 0;
+        let content = MarkdownLine::get_content(line, &line_type, indent);
+        assert_eq!(content, "");
+
+        let line = "- 日本語 text".to_string();
+        let line_type = MarkDownLineType::Li;
+        let indent = 0;
+        let content = MarkdownLine::get_content(line, &line_type, indent);
+        assert_eq!(content, "日本語 text");
+
+        let line = "\u{a0}\u{a0}\u{a0}- café".to_string();
+        let line_type = MarkDownLineType::Li;
+        let indent = 1;
+        let content = MarkdownLine::get_content(line, &line_type, indent);
+        assert_eq!(content, "café");
+
+        let line = "\t-\t😀 done".to_string();
+        let line_type = MarkDownLineType::Li;
+        let indent = 1;
+        let content = MarkdownLine::get_content(line, &line_type, indent);
+        assert_eq!(content, "😀 done");
+    }
+
+    #[test]
+    fn tabs_and_unicode() {
+        assert_eq!(MarkdownLine::get_indent("\t- bullet".to_string()), 1);
+        assert_eq!(MarkdownLine::get_indent("  \t- bullet".to_string()), 1);
+        assert_eq!(MarkdownLine::get_indent("\t\t- bullet".to_string()), 2);
+        assert_eq!(MarkdownLine::get_indent("\t1. step".to_string()), 1);
+
+        let md_line = MarkdownLine::parse("-\tfirst".to_string());
+        assert!(matches!(md_line.line_type, MarkDownLineType::Li));
+        assert_eq!(md_line.content, "first");
+
+        let md_line = MarkdownLine::parse("#\tÜberschrift".to_string());
+        assert!(matches!(md_line.line_type, MarkDownLineType::H1));
+        assert_eq!(md_line.content, "Überschrift");
+
+        let md_line = MarkdownLine::parse("2)\tzweite".to_string());
+        assert!(matches!(md_line.line_type, MarkDownLineType::OrderedLi(2)));
+        assert_eq!(md_line.content, "zweite");
+
+        assert_eq!(
+            render_html(&crate::parse("- a\n\t- b\n\t\t- c")),
+            concat!(
+                r#"<ul class="md-ul"><li class="md-li">a<ul class="md-ul">"#,
+                r#"<li class="md-li">b<ul class="md-ul"><li class="md-li">c</li></ul></li>"#,
+                r#"</ul></li></ul>"#,
+            )
+        );
     }
 
     #[test]
@@ -386,58 +1666,40 @@ mod tests {
         let md_line = MarkdownLine::parse(line);
         assert_eq!(md_line.content, "hey this is a line".to_string());
         assert_eq!(md_line.indent, 0);
-        assert!(match md_line.line_type {
-            MarkDownLineType::NoTag => true,
-            _ => false,
-        });
+        assert!(matches!(md_line.line_type, MarkDownLineType::NoTag));
 
         let line = "#hey this is a line".to_string();
         let md_line = MarkdownLine::parse(line);
         assert_eq!(md_line.content, "#hey this is a line".to_string());
         assert_eq!(md_line.indent, 0);
-        assert!(match md_line.line_type {
-            MarkDownLineType::NoTag => true,
-            _ => false,
-        });
+        assert!(matches!(md_line.line_type, MarkDownLineType::NoTag));
 
         let line = "# hey this is a line".to_string();
         let md_line = MarkdownLine::parse(line);
         assert_eq!(md_line.content, "hey this is a line".to_string());
         assert_eq!(md_line.indent, 0);
-        assert!(match md_line.line_type {
-            MarkDownLineType::H1 => true,
-            _ => false,
-        });
+        assert!(matches!(md_line.line_type, MarkDownLineType::H1));
 
         let line = "## hey this is a line".to_string();
         let md_line = MarkdownLine::parse(line);
 
         assert_eq!(md_line.content, "hey this is a line".to_string());
         assert_eq!(md_line.indent, 0);
-        assert!(match md_line.line_type {
-            MarkDownLineType::H2 => true,
-            _ => false,
-        });
+        assert!(matches!(md_line.line_type, MarkDownLineType::H2));
 
         let line = "".to_string();
         let md_line = MarkdownLine::parse(line);
 
         assert_eq!(md_line.content, "".to_string());
         assert_eq!(md_line.indent, -1);
-        assert!(match md_line.line_type {
-            MarkDownLineType::EmptyLine => true,
-            _ => false,
-        });
+        assert!(matches!(md_line.line_type, MarkDownLineType::EmptyLine));
 
         let line = "- mybullet".to_string();
         let md_line = MarkdownLine::parse(line);
 
         assert_eq!(md_line.content, "mybullet".to_string());
         assert_eq!(md_line.indent, 0);
-        assert!(match md_line.line_type {
-            MarkDownLineType::Li => true,
-            _ => false,
-        });
+        assert!(matches!(md_line.line_type, MarkDownLineType::Li));
     }
 
     #[test]
@@ -454,46 +1716,712 @@ mod tests {
 
         // make sure content is correct
         assert!(header.content == "My Header");
-        assert!(blank1.content == "");
+        assert!(blank1.content.is_empty());
         assert!(my_other_header.content == "My Other Header");
-        assert!(blank2.content == "");
+        assert!(blank2.content.is_empty());
         assert!(item_1.content == "item1");
         assert!(item_2.content == "item2");
 
         // make sure types are correct
-        assert!(match header.line_type {
-            MarkDownLineType::H1 => true,
-            _ => false,
-        });
+        assert!(matches!(header.line_type, MarkDownLineType::H1));
 
-        assert!(match blank1.line_type {
-            MarkDownLineType::EmptyLine => true,
-            _ => false,
-        });
+        assert!(matches!(blank1.line_type, MarkDownLineType::EmptyLine));
 
-        assert!(match my_other_header.line_type {
-            MarkDownLineType::H2 => true,
-            _ => false,
-        });
-        assert!(match blank2.line_type {
-            MarkDownLineType::EmptyLine => true,
-            _ => false,
-        });
-        assert!(match item_1.line_type {
-            MarkDownLineType::Li => true,
-            _ => false,
-        });
-        assert!(match item_2.line_type {
-            MarkDownLineType::Li => true,
-            _ => false,
-        });
+        assert!(matches!(my_other_header.line_type, MarkDownLineType::H2));
+        assert!(matches!(blank2.line_type, MarkDownLineType::EmptyLine));
+        assert!(matches!(item_1.line_type, MarkDownLineType::Li));
+        assert!(matches!(item_2.line_type, MarkDownLineType::Li));
     }
 
     #[test]
     fn test_markdown_parser() {
         let test_file = fs::read_to_string("./src/data/overall-test-file.md").unwrap();
         let test_html_file = fs::read_to_string("./src/data/overall-test-file.html").unwrap();
-        let val = MarkdownLine::parse_markdown_to_html(test_file);
+        let val = render_html(&crate::parse(&test_file));
         assert_eq!(val, test_html_file)
     }
+
+    #[test]
+    fn code_fence() {
+        let md_line = MarkdownLine::parse("```rust".to_string());
+        assert!(matches!(md_line.line_type, MarkDownLineType::CodeFence));
+        assert_eq!(md_line.content, "rust");
+
+        let md_line = MarkdownLine::parse("~~~".to_string());
+        assert!(matches!(md_line.line_type, MarkDownLineType::CodeFence));
+
+        let md_line = MarkdownLine::parse("``not a fence".to_string());
+        assert!(matches!(md_line.line_type, MarkDownLineType::NoTag));
+
+        let md_line = MarkdownLine::parse("``` rust`".to_string());
+        assert!(matches!(md_line.line_type, MarkDownLineType::NoTag));
+    }
+
+    #[test]
+    fn code_fence_to_html() {
+        let test_file = fs::read_to_string("./src/data/code-fence.md").unwrap();
+        let html = render_html(&crate::parse(&test_file));
+        let lines: Vec<&str> = html.split('\n').collect();
+
+        assert_eq!(
+            lines[2..4],
+            [
+                r#"<pre><code class="language-rust"># not a header"#,
+                "- not a bullet",
+            ]
+        );
+        assert_eq!(lines[4], "</code></pre>");
+        assert_eq!(lines[6], "<pre><code>```");
+        assert_eq!(lines[7], "still code");
+        assert_eq!(lines[8], "</code></pre>");
+    }
+
+    #[test]
+    fn unclosed_code_fence() {
+        let html = render_html(&crate::parse("  ```\n  # code\n    indented"));
+        assert_eq!(html, "<pre><code># code\n  indented\n</code></pre>");
+    }
+
+    #[test]
+    fn nested_list_item() {
+        let md_line = MarkdownLine::parse("    - nested bullet".to_string());
+        assert_eq!(md_line.content, "nested bullet");
+        assert_eq!(md_line.indent, 1);
+        assert!(matches!(md_line.line_type, MarkDownLineType::Li));
+
+        let md_line = MarkdownLine::parse("  - two space bullet".to_string());
+        assert_eq!(md_line.content, "two space bullet");
+        assert_eq!(md_line.indent, 1);
+        assert!(matches!(md_line.line_type, MarkDownLineType::Li));
+    }
+
+    #[test]
+    fn nested_list_to_html() {
+        let test_file = fs::read_to_string("./src/data/nested-list.md").unwrap();
+        let html = render_html(&crate::parse(&test_file));
+        let lines: Vec<&str> = html.split('\n').collect();
+
+        assert_eq!(
+            lines[0],
+            concat!(
+                r#"<ul class="md-ul">"#,
+                r#"<li class="md-li">bullet 1<ul class="md-ul">"#,
+                r#"<li class="md-li">bullet 1.1<ul class="md-ul">"#,
+                r#"<li class="md-li">bullet 1.1.1</li>"#,
+                r#"</ul></li>"#,
+                r#"<li class="md-li">bullet 1.2</li>"#,
+                r#"</ul></li>"#,
+                r#"<li class="md-li">bullet 2</li>"#,
+                r#"<li class="md-li">bullet 3</li>"#,
+                r#"</ul>"#,
+            )
+        );
+        assert_eq!(lines[1], r#"<div class="md-empty-line"></div>"#);
+        assert_eq!(lines[2], r#"<p class="md-p">Paragraph</p>"#);
+        assert_eq!(lines[3], r#"<div class="md-empty-line"></div>"#);
+        assert_eq!(
+            lines[4],
+            concat!(
+                r#"<ul class="md-ul">"#,
+                r#"<li class="md-li">two spaces<ul class="md-ul">"#,
+                r#"<li class="md-li">nested<ul class="md-ul">"#,
+                r#"<li class="md-li">deeper</li>"#,
+                r#"</ul></li>"#,
+                r#"<li class="md-li">back</li>"#,
+                r#"</ul></li>"#,
+                r#"</ul>"#,
+            )
+        );
+    }
+
+    #[test]
+    fn ordered_list_item() {
+        let md_line = MarkdownLine::parse("12. twelfth step".to_string());
+        assert_eq!(md_line.content, "twelfth step");
+        assert_eq!(md_line.indent, 0);
+        assert!(matches!(md_line.line_type, MarkDownLineType::OrderedLi(12)));
+
+        let md_line = MarkdownLine::parse("    1) nested step".to_string());
+        assert_eq!(md_line.content, "nested step");
+        assert_eq!(md_line.indent, 1);
+        assert!(matches!(md_line.line_type, MarkDownLineType::OrderedLi(1)));
+
+        let md_line = MarkdownLine::parse("1.not a step".to_string());
+        assert!(matches!(md_line.line_type, MarkDownLineType::NoTag));
+
+        let md_line = MarkdownLine::parse("1. ".to_string());
+        assert!(matches!(md_line.line_type, MarkDownLineType::NoTag));
+    }
+
+    #[test]
+    fn ordered_list_to_html() {
+        let test_file = fs::read_to_string("./src/data/ordered-list.md").unwrap();
+        let html = render_html(&crate::parse(&test_file));
+        let lines: Vec<&str> = html.split('\n').collect();
+
+        assert_eq!(
+            lines[0],
+            concat!(
+                r#"<ol class="md-ol" start="3">"#,
+                r#"<li class="md-li">step three</li>"#,
+                r#"<li class="md-li">step four<ul class="md-ul">"#,
+                r#"<li class="md-li">detail</li>"#,
+                r#"<li class="md-li">detail<ol class="md-ol">"#,
+                r#"<li class="md-li">sub step</li>"#,
+                r#"</ol></li>"#,
+                r#"</ul></li>"#,
+                r#"</ol>"#,
+            )
+        );
+        assert_eq!(
+            lines[1],
+            concat!(
+                r#"<ul class="md-ul">"#,
+                r#"<li class="md-li">bullet<ol class="md-ol">"#,
+                r#"<li class="md-li">numbered</li>"#,
+                r#"</ol></li>"#,
+                r#"</ul>"#,
+            )
+        );
+        assert_eq!(
+            lines[2],
+            r#"<ol class="md-ol"><li class="md-li">new list</li></ol>"#
+        );
+    }
+
+    #[test]
+    fn inline_formatting_in_blocks() {
+        let html = render_html(&crate::parse("## A **bold** header\n- some `code`"));
+        assert_eq!(
+            html,
+            concat!(
+                r#"<h2 id="a-bold-header" class="md-h2">A <strong>bold</strong> header</h2>"#,
+                "\n",
+                r#"<ul class="md-ul"><li class="md-li">some <code>code</code></li></ul>"#,
+            )
+        );
+    }
+
+    #[test]
+    fn reference_links_to_html() {
+        let test_file = fs::read_to_string("./src/data/reference-links.md").unwrap();
+        let html = render_html(&crate::parse(&test_file));
+        let lines: Vec<&str> = html.split('\n').collect();
+
+        assert_eq!(
+            lines[0],
+            r#"<p class="md-p">Read the <a href="https://example.com/guide" title="The Guide">guide</a> first.</p>"#
+        );
+        assert_eq!(
+            lines[1],
+            r#"<ul class="md-ul"><li class="md-li"><a href="/api">API Reference</a></li></ul>"#
+        );
+        assert_eq!(lines[2], r#"<pre><code>[not]: /a-definition"#);
+        assert_eq!(lines[4], r#"<div class="md-empty-line"></div>"#);
+        assert_eq!(lines.len(), 6);
+    }
+
+    #[test]
+    fn definitions_in_quoted_fences() {
+        let html = render_html(&crate::parse(
+            "> ```\n> [a]: /x\n> ```\n\n[a]\n\n> > [b]: /y\n\n[b]",
+        ));
+        let lines: Vec<&str> = html.split('\n').collect();
+
+        assert_eq!(lines[3], r#"<p class="md-p">[a]</p>"#);
+        assert_eq!(lines[7], r#"<p class="md-p"><a href="/y">b</a></p>"#);
+    }
+
+    #[test]
+    fn setext_headers() {
+        let html = render_html(&crate::parse(
+            "Title\n=====\nSubtitle *here*\n  ---\n\n==\n##### Five\n###### Six ##",
+        ));
+        let lines: Vec<&str> = html.split('\n').collect();
+
+        assert_eq!(
+            lines,
+            [
+                r#"<div class="md-h1-container"><h1 id="title" class="md-h1">Title</h1><hr class="md-hr"></div>"#,
+                r#"<h2 id="subtitle-here" class="md-h2">Subtitle <em>here</em></h2>"#,
+                r#"<div class="md-empty-line"></div>"#,
+                r#"<p class="md-p">==</p>"#,
+                r#"<h5 id="five" class="md-h5">Five</h5>"#,
+                r#"<h6 id="six" class="md-h6">Six</h6>"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn paragraphs() {
+        let test_file = fs::read_to_string("./src/data/paragraphs.md").unwrap();
+        let html = render_html(&crate::parse(&test_file));
+        let lines: Vec<&str> = html.split('\n').collect();
+
+        assert_eq!(
+            lines,
+            [
+                r#"<p class="md-p">A paragraph wrapped"#,
+                r#"over <em>three"#,
+                r#"lines</em>.</p>"#,
+                r#"<div class="md-empty-line"></div>"#,
+                r#"<p class="md-p">Hard<br>"#,
+                r#"breaks<br>"#,
+                r#"here, but not at the end\</p>"#,
+                r#"<div class="md-empty-line"></div>"#,
+                r#"<h2 id="a-two-line-header" class="md-h2">A two"#,
+                r#"line header</h2>"#,
+                r#"<div class="md-empty-line"></div>"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn blockquote_line() {
+        let md_line = MarkdownLine::parse("> quoted".to_string());
+        assert!(matches!(md_line.line_type, MarkDownLineType::BlockQuote));
+        assert_eq!(md_line.content, "quoted");
+
+        let md_line = MarkdownLine::parse("   >>nested".to_string());
+        assert!(matches!(md_line.line_type, MarkDownLineType::BlockQuote));
+        assert_eq!(md_line.content, ">nested");
+
+        let md_line = MarkdownLine::parse("    > code".to_string());
+        assert!(!matches!(md_line.line_type, MarkDownLineType::BlockQuote));
+    }
+
+    #[test]
+    fn deeply_nested_blockquotes() {
+        let html = render_html(&crate::parse(&format!("{} deep", ">".repeat(5000))));
+        let opening = r#"<blockquote class="md-blockquote">"#;
+        assert!(html.starts_with(&opening.repeat(MAX_QUOTE_DEPTH)));
+        assert_eq!(html.matches(opening).count(), MAX_QUOTE_DEPTH);
+        assert!(html.contains(&format!(
+            r#"<p class="md-p">{} deep</p>"#,
+            "&gt;".repeat(4900)
+        )));
+    }
+
+    #[test]
+    fn blockquotes_to_html() {
+        let test_file = fs::read_to_string("./src/data/blockquote.md").unwrap();
+        let html = render_html(&crate::parse(&test_file));
+        let lines: Vec<&str> = html.split('\n').collect();
+
+        assert_eq!(
+            lines,
+            [
+                concat!(
+                    r#"<blockquote class="md-blockquote">"#,
+                    r#"<h2 id="feedback" class="md-h2">Feedback</h2>"#,
+                    r#"<p class="md-p">The new <strong>export</strong> is great,"#,
+                ),
+                concat!(
+                    r#"and so fast.</p>"#,
+                    r#"<div class="md-empty-line"></div>"#,
+                    r#"<ul class="md-ul"><li class="md-li">one</li><li class="md-li">two</li></ul>"#,
+                    r#"<pre><code class="language-sh">make export"#,
+                ),
+                concat!(
+                    r#"</code></pre>"#,
+                    r#"<blockquote class="md-blockquote"><p class="md-p">Nested quote"#,
+                ),
+                r#"with a lazy line</p></blockquote></blockquote>"#,
+                r#"<div class="md-empty-line"></div>"#,
+                r#"<p class="md-p">Back <a href="/notes">outside</a>.</p>"#,
+                r#"<div class="md-empty-line"></div>"#,
+                r#"<blockquote class="md-blockquote"></blockquote>"#,
+                r#"<div class="md-empty-line"></div>"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn thematic_breaks() {
+        for line in ["---", "***", "___", " - - -", "   *  *  *  ", "-----"] {
+            let md_line = MarkdownLine::parse(line.to_string());
+            assert!(matches!(md_line.line_type, MarkDownLineType::ThematicBreak));
+        }
+        for line in ["--", "- - x", "-*-", "    ---", "--- bullet"] {
+            let md_line = MarkdownLine::parse(line.to_string());
+            assert!(!matches!(
+                md_line.line_type,
+                MarkDownLineType::ThematicBreak
+            ));
+        }
+    }
+
+    #[test]
+    fn thematic_breaks_to_html() {
+        let html = render_html(&crate::parse(
+            "- item\n- - -\nSetext wins\n---\nParagraph\n\n***",
+        ));
+        let lines: Vec<&str> = html.split('\n').collect();
+
+        assert_eq!(
+            lines,
+            [
+                r#"<ul class="md-ul"><li class="md-li">item</li></ul>"#,
+                r#"<hr class="md-hr">"#,
+                r#"<h2 id="setext-wins" class="md-h2">Setext wins</h2>"#,
+                r#"<p class="md-p">Paragraph</p>"#,
+                r#"<div class="md-empty-line"></div>"#,
+                r#"<hr class="md-hr">"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn xhtml_flavor() {
+        let html = render_html_with_options(
+            &crate::parse("# Title\nhard  \nbreak ![x](/x.png)"),
+            RenderOptions {
+                flavor: HtmlFlavor::Xhtml,
+                ..RenderOptions::default()
+            },
+        );
+        let lines: Vec<&str> = html.split('\n').collect();
+
+        assert_eq!(
+            lines,
+            [
+                r#"<div class="md-h1-container"><h1 id="title" class="md-h1">Title</h1><hr class="md-hr"/></div>"#,
+                r#"<p class="md-p">hard<br/>"#,
+                r#"break <img src="/x.png" alt="x"/></p>"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn table_rows() {
+        assert_eq!(split_table_row("| a | b |"), ["a", "b"]);
+        assert_eq!(split_table_row("a|b"), ["a", "b"]);
+        assert_eq!(split_table_row(r"| a \| b | c \|"), ["a | b", "c |"]);
+        assert_eq!(
+            parse_delimiter_row("| --- | :-- | :-: | --: |"),
+            Some(vec![
+                Alignment::None,
+                Alignment::Left,
+                Alignment::Center,
+                Alignment::Right,
+            ])
+        );
+        assert_eq!(
+            parse_delimiter_row("---|---"),
+            Some(vec![Alignment::None; 2])
+        );
+        assert_eq!(parse_delimiter_row("---"), None);
+        assert_eq!(parse_delimiter_row("| -x- |"), None);
+    }
+
+    #[test]
+    fn tables_to_html() {
+        let test_file = fs::read_to_string("./src/data/table.md").unwrap();
+        let html = render_html(&crate::parse(&test_file));
+        let lines: Vec<&str> = html.split('\n').collect();
+
+        assert_eq!(
+            lines,
+            [
+                concat!(
+                    r#"<table class="md-table"><thead><tr>"#,
+                    r#"<th class="md-align-left">Endpoint</th>"#,
+                    r#"<th class="md-align-center">Method</th>"#,
+                    r#"<th>Auth</th>"#,
+                    r#"<th class="md-align-right">Limit</th>"#,
+                    r#"</tr></thead><tbody><tr>"#,
+                    r#"<td class="md-align-left"><code>/users</code></td>"#,
+                    r#"<td class="md-align-center">GET</td>"#,
+                    r#"<td><strong>yes</strong></td>"#,
+                    r#"<td class="md-align-right">100</td>"#,
+                    r#"</tr><tr>"#,
+                    r#"<td class="md-align-left"><code>/login</code></td>"#,
+                    r#"<td class="md-align-center">POST</td>"#,
+                    r#"<td>no</td>"#,
+                    r#"<td class="md-align-right"></td>"#,
+                    r#"</tr><tr>"#,
+                    r#"<td class="md-align-left"><code>a|b</code></td>"#,
+                    r#"<td class="md-align-center">PUT</td>"#,
+                    r#"<td>yes</td>"#,
+                    r#"<td class="md-align-right">5</td>"#,
+                    r#"</tr><tr>"#,
+                    r#"<td class="md-align-left">Rows continue until a blank line</td>"#,
+                    r#"<td class="md-align-center"></td>"#,
+                    r#"<td></td>"#,
+                    r#"<td class="md-align-right"></td>"#,
+                    r#"</tr></tbody></table>"#,
+                ),
+                r#"<div class="md-empty-line"></div>"#,
+            ]
+        );
+
+        assert_eq!(
+            render_html(&crate::parse("Intro\nName | Value\n--- | ---")),
+            concat!(
+                r#"<p class="md-p">Intro</p>"#,
+                "\n",
+                r#"<table class="md-table"><thead><tr><th>Name</th><th>Value</th></tr></thead></table>"#,
+            )
+        );
+        assert_eq!(
+            render_html(&crate::parse("a | b\n--- | --- | ---")),
+            "<p class=\"md-p\">a | b\n--- | --- | ---</p>"
+        );
+    }
+
+    #[test]
+    fn task_lists_to_html() {
+        let html = render_html(&crate::parse(
+            "- [x] Write *notes*\n- [ ] Tag\n    - [X] Bump\n- [] not a task\n- [ ]",
+        ));
+
+        assert_eq!(
+            html,
+            concat!(
+                r#"<ul class="md-ul">"#,
+                r#"<li class="md-li md-task"><input type="checkbox" checked="" disabled=""> Write <em>notes</em></li>"#,
+                r#"<li class="md-li md-task"><input type="checkbox" disabled=""> Tag<ul class="md-ul">"#,
+                r#"<li class="md-li md-task"><input type="checkbox" checked="" disabled=""> Bump</li></ul></li>"#,
+                r#"<li class="md-li">[] not a task</li>"#,
+                r#"<li class="md-li">[ ]</li>"#,
+                r#"</ul>"#,
+            )
+        );
+    }
+
+    #[test]
+    fn get_tasks() {
+        let test_file = fs::read_to_string("./src/data/task-list.md").unwrap();
+        let tasks: Vec<(bool, String, usize)> = crate::parse(&test_file)
+            .tasks()
+            .into_iter()
+            .map(|task| (task.checked, task.text, task.line))
+            .collect();
+
+        assert_eq!(
+            tasks,
+            [
+                (true, "Write notes".to_string(), 2),
+                (false, "Tag the release".to_string(), 3),
+                (true, "Bump version".to_string(), 4),
+                (false, "Update changelog".to_string(), 5),
+                (false, "Announce".to_string(), 8),
+                (false, "Quoted task".to_string(), 12),
+            ]
+        );
+
+        let tasks = crate::parse("text\n\n    - [ ] x").tasks();
+        assert!(tasks.is_empty());
+    }
+
+    #[test]
+    fn columns() {
+        assert_eq!(leading_columns("    a"), 4);
+        assert_eq!(leading_columns("\ta"), 4);
+        assert_eq!(leading_columns("  \ta"), 4);
+        assert_eq!(leading_columns("a    "), 0);
+        assert_eq!(strip_columns("      a", 4), "  a");
+        assert_eq!(strip_columns("\t\ta", 4), "\ta");
+        assert_eq!(strip_columns("  \ta", 2), "\ta");
+        assert_eq!(strip_columns(" \ta", 2), "  a");
+        assert_eq!(strip_columns("  ", 4), "");
+    }
+
+    #[test]
+    fn indented_code_to_html() {
+        let test_file = fs::read_to_string("./src/data/indented-code.md").unwrap();
+        let html = render_html(&crate::parse(&test_file));
+        let lines: Vec<&str> = html.split('\n').collect();
+
+        assert_eq!(
+            lines,
+            [
+                r#"<pre><code>fn main() {"#,
+                r#"    println!(&quot;&lt;hi&gt;&quot;);"#,
+                r#""#,
+                r#"}"#,
+                r#"</code></pre>"#,
+                r#"<div class="md-empty-line"></div>"#,
+                r#"<p class="md-p">Text"#,
+                r#"lazy continuation</p>"#,
+                r#"<div class="md-empty-line"></div>"#,
+                concat!(
+                    r#"<ul class="md-ul"><li class="md-li">item"#,
+                    r#"<pre><code>item code"#,
+                ),
+                concat!(
+                    r#"</code></pre>"#,
+                    r#"<ul class="md-ul"><li class="md-li">nested"#,
+                    r#"<pre><code>nested code"#,
+                ),
+                r#"</code></pre></li></ul></li></ul>"#,
+                r#"<div class="md-empty-line"></div>"#,
+                r#"<pre><code># not a header"#,
+                r#"</code></pre>"#,
+                r#"<div class="md-empty-line"></div>"#,
+                r#"<ul class="md-ul"><li class="md-li">a long item that"#,
+                r#"wraps onto this line</li></ul>"#,
+                r#"<ol class="md-ol"><li class="md-li">an ordered item that"#,
+                r#"wraps too</li></ol>"#,
+                r#"<div class="md-empty-line"></div>"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn list_paragraphs_to_html() {
+        let html = render_html(&crate::parse(
+            "- a\n\n  para\nlazy\n\n- b\n  - c\n\n  in b\n\nout",
+        ));
+        let lines: Vec<&str> = html.split('\n').collect();
+
+        assert_eq!(
+            lines,
+            [
+                r#"<ul class="md-ul"><li class="md-li">a<p class="md-p">para"#,
+                r#"lazy</p></li><li class="md-li">b<ul class="md-ul"><li class="md-li">c</li></ul><p class="md-p">in b</p></li></ul>"#,
+                r#"<div class="md-empty-line"></div>"#,
+                r#"<p class="md-p">out</p>"#,
+            ]
+        );
+
+        let html = render_html(&crate::parse("- a\n\n  # title"));
+        assert!(html.starts_with(r#"<ul class="md-ul"><li class="md-li">a</li></ul>"#));
+    }
+
+    #[test]
+    fn diagnostics() {
+        let document = crate::parse(
+            "#Title\n####### Deep\n   ## Indented\n> text\n>  #tag\n    #code\n```\n#not checked",
+        );
+        let found: Vec<(usize, usize, DiagnosticKind)> = document
+            .diagnostics
+            .iter()
+            .map(|diagnostic| (diagnostic.line, diagnostic.column, diagnostic.kind))
+            .collect();
+
+        assert_eq!(
+            found,
+            [
+                (1, 1, DiagnosticKind::MissingHeaderSpace),
+                (2, 1, DiagnosticKind::HeaderTooDeep),
+                (3, 4, DiagnosticKind::IndentedHeader),
+                (5, 4, DiagnosticKind::MissingHeaderSpace),
+                (7, 1, DiagnosticKind::UnclosedCodeFence),
+            ]
+        );
+        assert_eq!(
+            document.diagnostics[1].message,
+            "header level too deep, rendered as paragraph"
+        );
+        assert!(render_html(&document).starts_with(r#"<p class="md-p">#Title"#));
+
+        let document = crate::parse("# Fine\n- #3 in the list");
+        assert!(document.diagnostics.is_empty());
+    }
+
+    /// Markdown syntax mixed with tabs, multi-byte whitespace and
+    /// multi-byte text, to reach the block and inline parsers more often than
+    /// arbitrary strings do.
+    const MARKDOWN_LINE: &str =
+        r"[ \t\u{a0}\u{3000}#>*_`~|:.)(\[\]!<\\-]{0,6}[0-9a-zé😀 \t\u{a0}#*_`|\\-]{0,8}";
+
+    proptest! {
+        #[test]
+        fn parse_never_panics(line in any::<String>()) {
+            MarkdownLine::parse(line);
+        }
+
+        #[test]
+        fn markdown_line_never_panics(line in MARKDOWN_LINE) {
+            MarkdownLine::parse(line);
+        }
+
+        #[test]
+        fn documents_never_panic(lines in prop::collection::vec(MARKDOWN_LINE, 0..12)) {
+            render_html(&crate::parse(&lines.join("\n")));
+        }
+    }
+
+    #[test]
+    fn line_spans() {
+        let md_lines = MarkdownLine::get_md_lines("# Café\n\n  > naïve".to_string());
+        let spans: Vec<SourceSpan> = md_lines.iter().map(|md_line| md_line.span).collect();
+
+        assert_eq!(
+            spans,
+            [
+                SourceSpan {
+                    start_offset: 0,
+                    end_offset: 7,
+                    start_line: 1,
+                    start_column: 1,
+                    end_line: 1,
+                    end_column: 6,
+                },
+                SourceSpan {
+                    start_offset: 8,
+                    end_offset: 8,
+                    start_line: 2,
+                    start_column: 1,
+                    end_line: 2,
+                    end_column: 1,
+                },
+                SourceSpan {
+                    start_offset: 11,
+                    end_offset: 19,
+                    start_line: 3,
+                    start_column: 3,
+                    end_line: 3,
+                    end_column: 9,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn source_positions_to_html() {
+        let document = crate::parse(
+            "Some *text*\n  wrapped\n- a\n    - b\n> quote\n>  > deep\n```\ncode\n```\nTitle\n---",
+        );
+        let options = RenderOptions {
+            source_positions: true,
+            ..RenderOptions::default()
+        };
+        let html = render_html_with_options(&document, options);
+        let lines: Vec<&str> = html.split('\n').collect();
+
+        assert_eq!(
+            lines,
+            [
+                r#"<p class="md-p" data-sourcepos="1:1-2:9">Some <em>text</em>"#,
+                r#"wrapped</p>"#,
+                concat!(
+                    r#"<ul class="md-ul" data-sourcepos="3:1-4:7">"#,
+                    r#"<li class="md-li" data-sourcepos="3:1-4:7">a"#,
+                    r#"<ul class="md-ul" data-sourcepos="4:5-4:7">"#,
+                    r#"<li class="md-li" data-sourcepos="4:5-4:7">b</li></ul></li></ul>"#,
+                ),
+                concat!(
+                    r#"<blockquote class="md-blockquote" data-sourcepos="5:1-6:9">"#,
+                    r#"<p class="md-p" data-sourcepos="5:3-5:7">quote</p>"#,
+                    r#"<blockquote class="md-blockquote" data-sourcepos="6:4-6:9">"#,
+                    r#"<p class="md-p" data-sourcepos="6:6-6:9">deep</p></blockquote></blockquote>"#,
+                ),
+                r#"<pre data-sourcepos="7:1-9:3"><code>code"#,
+                r#"</code></pre>"#,
+                r#"<h2 id="title" class="md-h2" data-sourcepos="10:1-11:3">Title</h2>"#,
+            ]
+        );
+
+        let html = render_html_with_options(&crate::parse("a\n\n>  \n> b"), options);
+        assert_eq!(
+            html.split('\n').nth(2),
+            Some(concat!(
+                r#"<blockquote class="md-blockquote" data-sourcepos="3:1-4:3">"#,
+                r#"<div class="md-empty-line" data-sourcepos="3:3-3:3"></div>"#,
+                r#"<p class="md-p" data-sourcepos="4:3-4:3">b</p></blockquote>"#,
+            ))
+        );
+    }
 }
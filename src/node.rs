@@ -1,87 +1,156 @@
 use core::fmt;
 use std::fmt::Display;
+use std::str::FromStr;
 
-#[allow(dead_code)]
-pub enum Tag {
-    Div,
-    H1,
-    H2,
-    H3,
-    H4,
-    Hr,
-    Ol,
-    Ul,
-    Li,
-    P,
-    Code,
+/// Declares `Tag` and `TAG_NAMES` from one list, so a variant can't be added
+/// without its HTML name.
+macro_rules! tags {
+    ($($variant:ident => $name:literal,)*) => {
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum Tag {
+            $($variant,)*
+        }
+
+        /// Every `Tag` with its HTML element name.
+        pub const TAG_NAMES: &[(Tag, &str)] = &[$((Tag::$variant, $name),)*];
+    };
+}
+
+tags! {
+    Div => "div",
+    H1 => "h1",
+    H2 => "h2",
+    H3 => "h3",
+    H4 => "h4",
+    H5 => "h5",
+    H6 => "h6",
+    Hr => "hr",
+    Ol => "ol",
+    Ul => "ul",
+    Li => "li",
+    P => "p",
+    Pre => "pre",
+    Code => "code",
+    Strong => "strong",
+    Em => "em",
+    Del => "del",
+    A => "a",
+    Img => "img",
+    Nav => "nav",
+    Br => "br",
+    Blockquote => "blockquote",
+    Table => "table",
+    Thead => "thead",
+    Tbody => "tbody",
+    Tr => "tr",
+    Th => "th",
+    Td => "td",
+    Input => "input",
 }
 
 impl Display for Tag {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Tag::Div => {
-                write!(f, "div")
-            }
-            Tag::H1 => {
-                write!(f, "h1")
-            }
-            Tag::H2 => {
-                write!(f, "h2")
-            }
-            Tag::H3 => {
-                write!(f, "h3")
-            }
-            Tag::H4 => {
-                write!(f, "h4")
-            }
-            Tag::Hr => {
-                write!(f, "hr")
-            }
-            Tag::Ol => {
-                write!(f, "h3")
-            }
-            Tag::Ul => {
-                write!(f, "ul")
-            }
-            Tag::P => {
-                write!(f, "p")
-            }
-            Tag::Li => {
-                write!(f, "li")
-            }
-            Tag::Code => {
-                write!(f, "code")
-            }
-        }
+        write!(f, "{}", self.name())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct UnknownTag(pub String);
+
+impl Display for UnknownTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown tag name `{}`", self.0)
+    }
+}
+
+/// Parses an HTML element name, ignoring case, back into a `Tag`.
+impl FromStr for Tag {
+    type Err = UnknownTag;
+
+    fn from_str(name: &str) -> Result<Tag, UnknownTag> {
+        TAG_NAMES
+            .iter()
+            .find(|(_, tag_name)| tag_name.eq_ignore_ascii_case(name))
+            .map(|(tag, _)| *tag)
+            .ok_or_else(|| UnknownTag(name.to_string()))
     }
 }
 
-#[allow(dead_code)]
+impl Tag {
+    pub fn name(&self) -> &'static str {
+        TAG_NAMES
+            .iter()
+            .find(|(tag, _)| tag == self)
+            .map(|(_, name)| *name)
+            .unwrap()
+    }
+
+    /// Void elements never have content or a closing tag.
+    pub fn is_void(&self) -> bool {
+        matches!(self, Tag::Hr | Tag::Img | Tag::Br | Tag::Input)
+    }
+}
+
+/// How void elements are written out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HtmlFlavor {
+    /// HTML5 style, as in `<br>`.
+    Html5,
+    /// XHTML style, self-closing as in `<br/>`.
+    Xhtml,
+}
+
+#[allow(clippy::enum_variant_names)]
 pub enum Content {
     InnerText(String),
     InnerContent(Node),
+    /// Trusted HTML, written out as-is. Never put user input in here.
+    RawHtml(String),
 }
 
-impl Display for Content {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+/// Escapes the characters that are significant in HTML text and in
+/// double-quoted attribute values.
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl Content {
+    pub fn write_html(&self, f: &mut dyn fmt::Write, options: RenderOptions) -> fmt::Result {
         match &self {
-            Content::InnerContent(node) => {
-                write!(f, "{}", node)
-            }
+            Content::InnerContent(node) => node.write_html(f, options),
             Content::InnerText(text) => {
-                write!(f, "{}", text)
+                write!(f, "{}", escape_html(text))
+            }
+            Content::RawHtml(html) => {
+                write!(f, "{}", html)
             }
         }
     }
 }
 
+impl Display for Content {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.write_html(f, RenderOptions::default())
+    }
+}
+
 pub struct ClassList(pub Vec<String>);
 
 fn optional_attr(attr_label: String, attr_value: String) -> String {
     if attr_value.is_empty() {
         return "".to_string();
     }
-    return format!(" {}=\"{}\"", attr_label, attr_value);
+    format!(" {}=\"{}\"", attr_label, escape_html(&attr_value))
 }
 
 impl Display for ClassList {
@@ -98,42 +167,146 @@ impl Display for ClassList {
     }
 }
 
-#[allow(dead_code)]
+/// Any attributes besides `id` and `class`, rendered in insertion order.
+pub struct Attributes(pub Vec<(String, String)>);
+
+impl Display for Attributes {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (attr_label, attr_value) in &self.0 {
+            write!(f, " {}=\"{}\"", attr_label, escape_html(attr_value))?;
+        }
+        Ok(())
+    }
+}
+
+/// Where a line or block came from in the Markdown source. Lines and columns
+/// are 1-based and columns count characters, with the end column on the last
+/// character of the block. The offsets are a byte range. Only block nodes
+/// carry a span: the headings inside Setext containers, table cells and
+/// inline nodes have none.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SourceSpan {
+    pub start_offset: usize,
+    pub end_offset: usize,
+    pub start_line: usize,
+    pub start_column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+}
+
+impl SourceSpan {
+    /// The span from the start of this span to the end of `last`.
+    pub fn to(&self, last: &SourceSpan) -> SourceSpan {
+        SourceSpan {
+            start_offset: self.start_offset,
+            end_offset: last.end_offset,
+            start_line: self.start_line,
+            start_column: self.start_column,
+            end_line: last.end_line,
+            end_column: last.end_column,
+        }
+    }
+}
+
+/// Formats the span as CommonMark's `data-sourcepos` does: `l:c-l:c`.
+impl Display for SourceSpan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}-{}:{}",
+            self.start_line, self.start_column, self.end_line, self.end_column
+        )
+    }
+}
+
+/// Settings for writing a `Node` out as HTML.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RenderOptions {
+    pub flavor: HtmlFlavor,
+    /// Adds a `data-sourcepos` attribute to every block node that knows
+    /// where it came from. Inline nodes are left without one.
+    pub source_positions: bool,
+}
+
+impl Default for RenderOptions {
+    fn default() -> RenderOptions {
+        RenderOptions {
+            flavor: HtmlFlavor::Html5,
+            source_positions: false,
+        }
+    }
+}
+
 pub struct Node {
     pub content: Vec<Content>,
     pub tag_name: Tag,
     pub class_list: ClassList,
     pub id: String,
+    /// Where the node came from, for block nodes created by the parser.
+    pub source_span: Option<SourceSpan>,
+    pub attributes: Attributes,
 }
 
 pub struct NodeList(pub Vec<Node>);
 
-impl std::fmt::Display for NodeList {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl NodeList {
+    pub fn to_html(&self, options: RenderOptions) -> String {
         let node_vec = &self.0;
         let mut print_string = vec![];
 
         for node in node_vec {
-            print_string.push(format!("{}", node))
+            print_string.push(node.to_html(options))
         }
 
-        write!(f, "{}", print_string.join("\n"))
+        print_string.join("\n")
     }
 }
 
-impl Display for Node {
+impl std::fmt::Display for NodeList {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.to_html(RenderOptions::default()))
+    }
+}
+
+impl Node {
+    pub fn write_html(&self, f: &mut dyn fmt::Write, options: RenderOptions) -> fmt::Result {
         write!(
             f,
-            r#"<{tag_name}{id_tag}{class_tag}>{inner_content}</{tag_name}>"#,
+            r#"<{tag_name}{id_tag}{class_tag}{attributes}"#,
             tag_name = self.tag_name,
+            attributes = self.attributes,
             class_tag = optional_attr("class".to_string(), format!("{}", self.class_list)),
             id_tag = optional_attr("id".to_string(), self.id.to_string()),
-            inner_content = self
-                .content
-                .iter()
-                .fold("".to_string(), |acc, node| { acc + &format!("{}", node) })
-        )
+        )?;
+        if let (true, Some(span)) = (options.source_positions, self.source_span) {
+            write!(f, r#" data-sourcepos="{}""#, span)?;
+        }
+
+        if self.tag_name.is_void() {
+            return match options.flavor {
+                HtmlFlavor::Html5 => write!(f, ">"),
+                HtmlFlavor::Xhtml => write!(f, "/>"),
+            };
+        }
+
+        write!(f, ">")?;
+        for content in &self.content {
+            content.write_html(f, options)?;
+        }
+        write!(f, "</{}>", self.tag_name)
+    }
+
+    pub fn to_html(&self, options: RenderOptions) -> String {
+        let mut html = String::new();
+        // Writing to a `String` cannot fail.
+        self.write_html(&mut html, options).unwrap();
+        html
+    }
+}
+
+impl Display for Node {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.write_html(f, RenderOptions::default())
     }
 }
 
@@ -144,6 +317,8 @@ fn try_header() {
         content: vec![Content::InnerText("My Header".to_string())],
         class_list: ClassList(vec![]),
         id: "".to_string(),
+        source_span: None,
+        attributes: Attributes(vec![]),
     };
 
     let printed = format!("{}", header);
@@ -163,6 +338,8 @@ fn node_with_classlist() {
         content: vec![Content::InnerText("My Header".to_string())],
         class_list: ClassList(vec!["class-1".to_string(), "class-2".to_string()]),
         id: "".to_string(),
+        source_span: None,
+        attributes: Attributes(vec![]),
     };
 
     let printed = format!("{}", header);
@@ -176,6 +353,8 @@ fn node_with_id() {
         content: vec![Content::InnerText("My Header".to_string())],
         class_list: ClassList(vec![]),
         id: "header-1".to_string(),
+        source_span: None,
+        attributes: Attributes(vec![]),
     };
 
     let printed = format!("{}", header);
@@ -189,6 +368,8 @@ fn node_with_classlist_and_id() {
         content: vec![Content::InnerText("My Header".to_string())],
         class_list: ClassList(vec!["class-1".to_string(), "class-2".to_string()]),
         id: "header-1".to_string(),
+        source_span: None,
+        attributes: Attributes(vec![]),
     };
 
     let printed = format!("{}", header);
@@ -198,6 +379,24 @@ fn node_with_classlist_and_id() {
     )
 }
 
+#[test]
+fn node_with_attributes() {
+    let list = Node {
+        tag_name: Tag::Ol,
+        content: vec![],
+        class_list: ClassList(vec!["md-ol".to_string()]),
+        id: "".to_string(),
+        source_span: None,
+        attributes: Attributes(vec![
+            ("start".to_string(), "3".to_string()),
+            ("reversed".to_string(), "".to_string()),
+        ]),
+    };
+
+    let printed = format!("{}", list);
+    assert_eq!(printed, r#"<ol class="md-ol" start="3" reversed=""></ol>"#)
+}
+
 #[test]
 fn node_with_nested_node() {
     let header = Node {
@@ -205,6 +404,8 @@ fn node_with_nested_node() {
         content: vec![Content::InnerText("My Header".to_string())],
         class_list: ClassList(vec![]),
         id: "header-1".to_string(),
+        source_span: None,
+        attributes: Attributes(vec![]),
     };
 
     let div = Node {
@@ -212,8 +413,182 @@ fn node_with_nested_node() {
         tag_name: Tag::Div,
         class_list: ClassList(vec![]),
         id: "".to_string(),
+        source_span: None,
+        attributes: Attributes(vec![]),
     };
 
     let printed = format!("{}", div);
     assert_eq!(printed, r#"<div><h1 id="header-1">My Header</h1></div>"#)
 }
+
+#[test]
+fn escapes_text_and_attributes() {
+    let paragraph = Node {
+        tag_name: Tag::P,
+        content: vec![Content::InnerText(r#"a < b & "c""#.to_string())],
+        class_list: ClassList(vec![r#"x"><script>"#.to_string()]),
+        id: r#"""#.to_string(),
+        source_span: None,
+        attributes: Attributes(vec![("title".to_string(), "<&>".to_string())]),
+    };
+
+    let printed = format!("{}", paragraph);
+    assert_eq!(
+        printed,
+        r#"<p id="&quot;" class="x&quot;&gt;&lt;script&gt;" title="&lt;&amp;&gt;">a &lt; b &amp; &quot;c&quot;</p>"#
+    )
+}
+
+#[test]
+fn raw_html_is_not_escaped() {
+    let div = Node {
+        tag_name: Tag::Div,
+        content: vec![
+            Content::RawHtml("<span>trusted</span>".to_string()),
+            Content::InnerText("<span>".to_string()),
+        ],
+        class_list: ClassList(vec![]),
+        id: "".to_string(),
+        source_span: None,
+        attributes: Attributes(vec![]),
+    };
+
+    let printed = format!("{}", div);
+    assert_eq!(printed, "<div><span>trusted</span>&lt;span&gt;</div>")
+}
+
+#[test]
+fn void_elements() {
+    let paragraph = Node {
+        tag_name: Tag::P,
+        content: vec![
+            Content::InnerText("line".to_string()),
+            Content::InnerContent(Node {
+                tag_name: Tag::Br,
+                content: vec![],
+                class_list: ClassList(vec![]),
+                id: "".to_string(),
+                source_span: None,
+                attributes: Attributes(vec![]),
+            }),
+            Content::InnerContent(Node {
+                tag_name: Tag::Img,
+                content: vec![Content::InnerText("ignored".to_string())],
+                class_list: ClassList(vec![]),
+                id: "".to_string(),
+                source_span: None,
+                attributes: Attributes(vec![("src".to_string(), "/a.png".to_string())]),
+            }),
+        ],
+        class_list: ClassList(vec![]),
+        id: "".to_string(),
+        source_span: None,
+        attributes: Attributes(vec![]),
+    };
+
+    assert_eq!(
+        paragraph.to_html(RenderOptions::default()),
+        r#"<p>line<br><img src="/a.png"></p>"#
+    );
+    assert_eq!(
+        format!("{}", paragraph),
+        paragraph.to_html(RenderOptions::default())
+    );
+    assert_eq!(
+        paragraph.to_html(RenderOptions {
+            flavor: HtmlFlavor::Xhtml,
+            ..RenderOptions::default()
+        }),
+        r#"<p>line<br/><img src="/a.png"/></p>"#
+    );
+}
+
+#[test]
+fn tag_names_round_trip() {
+    for (tag, name) in TAG_NAMES {
+        assert_eq!(tag.name(), *name);
+        assert_eq!(format!("{}", tag), *name);
+        assert_eq!(name.parse::<Tag>(), Ok(*tag));
+        assert_eq!(name.to_uppercase().parse::<Tag>(), Ok(*tag));
+        assert_eq!(
+            TAG_NAMES.iter().filter(|(_, other)| other == name).count(),
+            1,
+            "`{}` is used by more than one tag",
+            name
+        );
+    }
+    assert_eq!("ol".parse::<Tag>(), Ok(Tag::Ol));
+}
+
+#[test]
+fn every_tag_renders_its_own_name() {
+    for (tag, name) in TAG_NAMES {
+        let node = Node {
+            tag_name: *tag,
+            content: vec![],
+            class_list: ClassList(vec![]),
+            id: "".to_string(),
+            source_span: None,
+            attributes: Attributes(vec![]),
+        };
+        let html = format!("{}", node);
+        let expected = if tag.is_void() {
+            format!("<{}>", name)
+        } else {
+            format!("<{}></{}>", name, name)
+        };
+        assert_eq!(html, expected);
+
+        let opening = html.trim_start_matches('<').split('>').next().unwrap();
+        assert_eq!(opening.parse::<Tag>(), Ok(*tag));
+    }
+}
+
+#[test]
+fn unknown_tag_names() {
+    assert_eq!(
+        "marquee".parse::<Tag>(),
+        Err(UnknownTag("marquee".to_string()))
+    );
+    assert_eq!("".parse::<Tag>(), Err(UnknownTag("".to_string())));
+}
+
+#[test]
+fn source_positions() {
+    let mut node = Node {
+        tag_name: Tag::P,
+        content: vec![Content::InnerText("text".to_string())],
+        class_list: ClassList(vec![]),
+        id: "".to_string(),
+        source_span: None,
+        attributes: Attributes(vec![]),
+    };
+    let options = RenderOptions {
+        source_positions: true,
+        ..RenderOptions::default()
+    };
+    assert_eq!(node.to_html(options), "<p>text</p>");
+
+    let first = SourceSpan {
+        start_offset: 2,
+        end_offset: 7,
+        start_line: 1,
+        start_column: 3,
+        end_line: 1,
+        end_column: 7,
+    };
+    let last = SourceSpan {
+        start_offset: 8,
+        end_offset: 12,
+        start_line: 2,
+        start_column: 1,
+        end_line: 2,
+        end_column: 4,
+    };
+    node.source_span = Some(first.to(&last));
+    assert_eq!(
+        node.to_html(options),
+        r#"<p data-sourcepos="1:3-2:4">text</p>"#
+    );
+    assert_eq!(format!("{}", node), "<p>text</p>");
+}
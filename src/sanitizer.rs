@@ -0,0 +1,296 @@
+use crate::node::{Attributes, ClassList, Content, Node, NodeList, Tag};
+
+/// An allowlist applied to a `NodeList` before it is rendered.
+///
+/// Elements whose tag is not allowed are unwrapped, keeping their content. A
+/// top-level block that is not allowed becomes a plain `<p>`, which is always
+/// permitted. Attributes that are not allowed are dropped, as are `href` and
+/// `src` values whose URL scheme is not allowed. Relative URLs are always kept.
+/// A disallowed `<img>` is replaced by its `alt` text. `Content::RawHtml` can't
+/// be checked against the allowlist, so it is always escaped into visible text.
+pub struct SanitizePolicy {
+    pub allowed_tags: Vec<Tag>,
+    pub allowed_attributes: Vec<String>,
+    pub allowed_url_schemes: Vec<String>,
+}
+
+fn to_strings(values: &[&str]) -> Vec<String> {
+    values.iter().map(|value| value.to_string()).collect()
+}
+
+/// The lowercased scheme of a URL, or `None` for a relative URL. Whitespace
+/// and control characters are ignored, like browsers do, so that
+/// `java\tscript:` is still seen as `javascript:`.
+fn url_scheme(url: &str) -> Option<String> {
+    let url: String = url
+        .chars()
+        .filter(|c| !c.is_whitespace() && !c.is_control())
+        .collect();
+    let end = url.find([':', '/', '?', '#'])?;
+    if url[end..].starts_with(':') {
+        Some(url[..end].to_lowercase())
+    } else {
+        None
+    }
+}
+
+impl SanitizePolicy {
+    /// For user-submitted comments: basic formatting, lists, code and links
+    /// to the web or email. No headings, images or ids.
+    pub fn strict_comments() -> SanitizePolicy {
+        SanitizePolicy {
+            allowed_tags: vec![
+                Tag::Div,
+                Tag::P,
+                Tag::Hr,
+                Tag::Ul,
+                Tag::Ol,
+                Tag::Li,
+                Tag::Pre,
+                Tag::Code,
+                Tag::Strong,
+                Tag::Em,
+                Tag::Del,
+                Tag::A,
+                Tag::Br,
+                Tag::Blockquote,
+                Tag::Table,
+                Tag::Thead,
+                Tag::Tbody,
+                Tag::Tr,
+                Tag::Th,
+                Tag::Td,
+                Tag::Input,
+            ],
+            allowed_attributes: to_strings(&[
+                "class", "href", "title", "start", "type", "checked", "disabled",
+            ]),
+            allowed_url_schemes: to_strings(&["http", "https", "mailto"]),
+        }
+    }
+
+    /// For documentation written by the team: every tag the parser produces,
+    /// but still no `javascript:` style URLs.
+    pub fn trusted_docs() -> SanitizePolicy {
+        SanitizePolicy {
+            allowed_tags: vec![
+                Tag::Div,
+                Tag::H1,
+                Tag::H2,
+                Tag::H3,
+                Tag::H4,
+                Tag::H5,
+                Tag::H6,
+                Tag::Hr,
+                Tag::Ol,
+                Tag::Ul,
+                Tag::Li,
+                Tag::P,
+                Tag::Pre,
+                Tag::Code,
+                Tag::Strong,
+                Tag::Em,
+                Tag::Del,
+                Tag::A,
+                Tag::Img,
+                Tag::Nav,
+                Tag::Br,
+                Tag::Blockquote,
+                Tag::Table,
+                Tag::Thead,
+                Tag::Tbody,
+                Tag::Tr,
+                Tag::Th,
+                Tag::Td,
+                Tag::Input,
+            ],
+            allowed_attributes: to_strings(&[
+                "id", "class", "href", "src", "alt", "title", "start", "type", "checked",
+                "disabled",
+            ]),
+            allowed_url_schemes: to_strings(&["http", "https", "mailto", "tel", "ftp"]),
+        }
+    }
+
+    pub fn sanitize(&self, node_list: NodeList) -> NodeList {
+        NodeList(
+            node_list
+                .0
+                .into_iter()
+                .map(|node| {
+                    if self.allowed_tags.contains(&node.tag_name) {
+                        self.clean_node(node)
+                    } else {
+                        self.clean_node(Node {
+                            class_list: ClassList(vec![]),
+                            content: node.content,
+                            id: "".to_string(),
+                            source_span: node.source_span,
+                            attributes: Attributes(vec![]),
+                            tag_name: Tag::P,
+                        })
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    fn is_attribute_allowed(&self, attr_label: &str) -> bool {
+        self.allowed_attributes
+            .iter()
+            .any(|allowed| allowed == attr_label)
+    }
+
+    fn is_url_allowed(&self, url: &str) -> bool {
+        match url_scheme(url) {
+            Some(scheme) => self.allowed_url_schemes.contains(&scheme),
+            None => true,
+        }
+    }
+
+    fn clean_node(&self, node: Node) -> Node {
+        let attributes = node
+            .attributes
+            .0
+            .into_iter()
+            .filter(|(attr_label, attr_value)| {
+                self.is_attribute_allowed(attr_label)
+                    && (!matches!(attr_label.as_str(), "href" | "src")
+                        || self.is_url_allowed(attr_value))
+            })
+            .collect();
+
+        Node {
+            class_list: if self.is_attribute_allowed("class") {
+                node.class_list
+            } else {
+                ClassList(vec![])
+            },
+            content: self.clean_content(node.content),
+            id: if self.is_attribute_allowed("id") {
+                node.id
+            } else {
+                "".to_string()
+            },
+            attributes: Attributes(attributes),
+            tag_name: node.tag_name,
+            source_span: node.source_span,
+        }
+    }
+
+    fn clean_content(&self, content: Vec<Content>) -> Vec<Content> {
+        let mut cleaned = vec![];
+        for item in content {
+            match item {
+                Content::InnerText(text) => cleaned.push(Content::InnerText(text)),
+                Content::RawHtml(html) => cleaned.push(Content::InnerText(html)),
+                Content::InnerContent(node) => {
+                    if self.allowed_tags.contains(&node.tag_name) {
+                        cleaned.push(Content::InnerContent(self.clean_node(node)));
+                    } else if node.tag_name == Tag::Img {
+                        let alt = node
+                            .attributes
+                            .0
+                            .into_iter()
+                            .find(|(label, _)| label == "alt");
+                        if let Some((_, alt)) = alt {
+                            cleaned.push(Content::InnerText(alt));
+                        }
+                    } else {
+                        cleaned.extend(self.clean_content(node.content));
+                    }
+                }
+            }
+        }
+        cleaned
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::node::{Attributes, ClassList, Content, Node, NodeList, Tag};
+    use crate::sanitizer::{url_scheme, SanitizePolicy};
+    use crate::{parse, render_html};
+
+    fn sanitize(markdown: &str, policy: &SanitizePolicy) -> String {
+        render_html(&parse(markdown).sanitize(policy))
+    }
+
+    fn raw_html_node() -> NodeList {
+        NodeList(vec![Node {
+            class_list: ClassList(vec![]),
+            content: vec![
+                Content::InnerText("before ".to_string()),
+                Content::RawHtml("<script>alert(1)</script>".to_string()),
+            ],
+            id: "".to_string(),
+            source_span: None,
+            attributes: Attributes(vec![]),
+            tag_name: Tag::P,
+        }])
+    }
+
+    #[test]
+    fn url_schemes() {
+        assert_eq!(url_scheme("https://example.com"), Some("https".to_string()));
+        assert_eq!(
+            url_scheme("JavaScript:alert(1)"),
+            Some("javascript".to_string())
+        );
+        assert_eq!(
+            url_scheme(" java\tscript:alert(1)"),
+            Some("javascript".to_string())
+        );
+        assert_eq!(url_scheme("/docs/a:b"), None);
+        assert_eq!(url_scheme("page?q=a:b"), None);
+        assert_eq!(url_scheme("#anchor"), None);
+    }
+
+    #[test]
+    fn strips_disallowed_urls() {
+        let policy = SanitizePolicy::strict_comments();
+        assert_eq!(
+            sanitize("[click](javascript:alert(1))", &policy),
+            r#"<p class="md-p"><a>click</a></p>"#
+        );
+        assert_eq!(
+            sanitize("[ok](https://example.com) [rel](/docs)", &policy),
+            r#"<p class="md-p"><a href="https://example.com">ok</a> <a href="/docs">rel</a></p>"#
+        );
+    }
+
+    #[test]
+    fn unwraps_disallowed_tags() {
+        let policy = SanitizePolicy::strict_comments();
+        assert_eq!(
+            sanitize("see ![a cat](/cat.png) here", &policy),
+            r#"<p class="md-p">see a cat here</p>"#
+        );
+        assert_eq!(
+            sanitize("## A *heading*", &policy),
+            r#"<p>A <em>heading</em></p>"#
+        );
+    }
+
+    #[test]
+    fn escapes_raw_html() {
+        for policy in [
+            SanitizePolicy::strict_comments(),
+            SanitizePolicy::trusted_docs(),
+        ] {
+            assert_eq!(
+                format!("{}", policy.sanitize(raw_html_node())),
+                "<p>before &lt;script&gt;alert(1)&lt;/script&gt;</p>"
+            );
+        }
+    }
+
+    #[test]
+    fn trusted_docs_keeps_images_and_headings() {
+        let policy = SanitizePolicy::trusted_docs();
+        assert_eq!(
+            sanitize("## Title ![logo](/logo.png)", &policy),
+            r#"<h2 id="title-logo" class="md-h2">Title <img src="/logo.png" alt="logo"></h2>"#
+        );
+    }
+}
@@ -0,0 +1,231 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::inline_parser::plain_text;
+use crate::node::{Attributes, ClassList, Content, Node, NodeList, Tag};
+
+/// A header of the document, as listed in its table of contents.
+pub struct Heading {
+    pub level: u8,
+    pub text: String,
+    pub id: String,
+}
+
+/// Every header of a document, in document order.
+pub struct Outline(pub Vec<Heading>);
+
+/// Turns header text into a GitHub-style anchor: lowercased, with spaces
+/// turned into hyphens and everything but letters, digits, `-` and `_`
+/// removed.
+pub fn slugify(text: &str) -> String {
+    text.trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            '-' | '_' => Some(c),
+            _ if c.is_alphanumeric() => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+fn heading_level(tag_name: Tag) -> Option<u8> {
+    match tag_name {
+        Tag::H1 => Some(1),
+        Tag::H2 => Some(2),
+        Tag::H3 => Some(3),
+        Tag::H4 => Some(4),
+        Tag::H5 => Some(5),
+        Tag::H6 => Some(6),
+        _ => None,
+    }
+}
+
+/// The ids handed out so far, and for each slug the next suffix to try.
+#[derive(Default)]
+struct UsedIds {
+    ids: HashSet<String>,
+    next_suffix: HashMap<String, usize>,
+}
+
+impl UsedIds {
+    /// Picks `slug`, or `slug-1`, `slug-2`, ... if it has already been used.
+    /// Suffixes already tried for a slug are never tried again, so a document
+    /// full of identical headers stays linear.
+    fn unique_id(&mut self, slug: String) -> String {
+        if self.ids.insert(slug.clone()) {
+            return slug;
+        }
+        let suffix = self.next_suffix.entry(slug.clone()).or_insert(1);
+        loop {
+            let id = format!("{}-{}", slug, suffix);
+            *suffix += 1;
+            if self.ids.insert(id.clone()) {
+                return id;
+            }
+        }
+    }
+}
+
+fn visit_node(node: &mut Node, outline: &mut Outline, used: &mut UsedIds) {
+    if let Some(level) = heading_level(node.tag_name) {
+        let text = plain_text(&node.content)
+            .split_whitespace()
+            .collect::<Vec<&str>>()
+            .join(" ");
+        let id = used.unique_id(slugify(&text));
+        node.id = id.clone();
+        outline.0.push(Heading { level, text, id });
+        return;
+    }
+    for content in node.content.iter_mut() {
+        if let Content::InnerContent(child) = content {
+            visit_node(child, outline, used);
+        }
+    }
+}
+
+/// Gives every header in the node list a unique slug id and returns the
+/// resulting outline.
+pub fn assign_heading_ids(node_list: &mut NodeList) -> Outline {
+    let mut outline = Outline(vec![]);
+    let mut used = UsedIds::default();
+    for node in node_list.0.iter_mut() {
+        visit_node(node, &mut outline, &mut used);
+    }
+    outline
+}
+
+fn toc_node(tag_name: Tag, content: Vec<Content>, attributes: Vec<(String, String)>) -> Node {
+    Node {
+        class_list: ClassList(vec![]),
+        content,
+        id: "".to_string(),
+        source_span: None,
+        attributes: Attributes(attributes),
+        tag_name,
+    }
+}
+
+/// A `<ul>` of links to the headings. Headings deeper than the one before
+/// them are nested in a child list, the same way list items are.
+fn toc_list(headings: &[Heading]) -> Node {
+    let mut items = vec![];
+    let mut index = 0;
+
+    while index < headings.len() {
+        let heading = &headings[index];
+        let nested_length = headings[index + 1..]
+            .iter()
+            .take_while(|nested| nested.level > heading.level)
+            .count();
+
+        let link = toc_node(
+            Tag::A,
+            vec![Content::InnerText(heading.text.clone())],
+            vec![("href".to_string(), format!("#{}", heading.id))],
+        );
+        let mut content = vec![Content::InnerContent(link)];
+        if nested_length > 0 {
+            let nested = &headings[index + 1..index + 1 + nested_length];
+            content.push(Content::InnerContent(toc_list(nested)));
+        }
+        items.push(Content::InnerContent(toc_node(Tag::Li, content, vec![])));
+        index += nested_length + 1;
+    }
+
+    toc_node(Tag::Ul, items, vec![])
+}
+
+impl Outline {
+    /// Renders the outline as a `<nav class="md-toc">` of nested lists.
+    pub fn to_nav(&self) -> Node {
+        let content = if self.0.is_empty() {
+            vec![]
+        } else {
+            vec![Content::InnerContent(toc_list(&self.0))]
+        };
+        Node {
+            class_list: ClassList(vec!["md-toc".to_string()]),
+            content,
+            id: "".to_string(),
+            source_span: None,
+            attributes: Attributes(vec![]),
+            tag_name: Tag::Nav,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::toc::slugify;
+    use crate::{parse, render_html};
+
+    #[test]
+    fn slugs() {
+        assert_eq!(slugify("Hello World"), "hello-world");
+        assert_eq!(slugify("What's new in v2.0?"), "whats-new-in-v20");
+        assert_eq!(slugify("snake_case & kebab-case"), "snake_case--kebab-case");
+        assert_eq!(slugify("Über Café"), "über-café");
+    }
+
+    #[test]
+    fn heading_ids() {
+        let html = render_html(&parse("## Setup\n## Setup\n### Setup 1\n## Setup"));
+        let lines: Vec<&str> = html.split('\n').collect();
+
+        assert_eq!(
+            lines,
+            [
+                r#"<h2 id="setup" class="md-h2">Setup</h2>"#,
+                r#"<h2 id="setup-1" class="md-h2">Setup</h2>"#,
+                r#"<h3 id="setup-1-1" class="md-h3">Setup 1</h3>"#,
+                r#"<h2 id="setup-2" class="md-h2">Setup</h2>"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn many_duplicate_heading_ids() {
+        let outline = parse(&"## x\n".repeat(5000)).outline;
+        assert_eq!(outline.0.len(), 5000);
+        assert_eq!(outline.0[0].id, "x");
+        assert_eq!(outline.0[4999].id, "x-4999");
+    }
+
+    #[test]
+    fn outline() {
+        let outline = parse("# Guide\n## Install *it*\n### From source\n## Use\n#### Deep").outline;
+        let entries: Vec<(u8, &str, &str)> = outline
+            .0
+            .iter()
+            .map(|heading| (heading.level, heading.text.as_str(), heading.id.as_str()))
+            .collect();
+
+        assert_eq!(
+            entries,
+            [
+                (1, "Guide", "guide"),
+                (2, "Install it", "install-it"),
+                (3, "From source", "from-source"),
+                (2, "Use", "use"),
+                (4, "Deep", "deep"),
+            ]
+        );
+        assert_eq!(
+            format!("{}", outline.to_nav()),
+            concat!(
+                r#"<nav class="md-toc"><ul>"#,
+                r##"<li><a href="#guide">Guide</a><ul>"##,
+                r##"<li><a href="#install-it">Install it</a><ul>"##,
+                r##"<li><a href="#from-source">From source</a></li>"##,
+                r#"</ul></li>"#,
+                r##"<li><a href="#use">Use</a><ul>"##,
+                r##"<li><a href="#deep">Deep</a></li>"##,
+                r#"</ul></li>"#,
+                r#"</ul></li>"#,
+                r#"</ul></nav>"#,
+            )
+        );
+    }
+}